@@ -1,5 +1,24 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use pid_allocator::PidAllocator;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use pid_allocator::{
+    relax::{RelaxStrategy, Spin},
+    PidAllocator, ShardedPidAllocator,
+};
+
+const THREADS: usize = 4;
+
+/// Yields to the OS scheduler while the lock is held, like `relax::Yield` with the `std` feature.
+struct YieldNow;
+
+impl RelaxStrategy for YieldNow {
+    fn relax() {
+        thread::yield_now();
+    }
+}
 
 fn pid_allocator_benchmark(c: &mut Criterion) {
     let allocator = PidAllocator::<32>::new();
@@ -11,5 +30,92 @@ fn pid_allocator_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, pid_allocator_benchmark);
+fn recycle_benchmark(c: &mut Criterion) {
+    let allocator = PidAllocator::<32>::new();
+    let _held: Vec<_> = (0..100).filter_map(|_| allocator.allocate()).collect();
+    c.bench_function("Pid::drop (recycle)", |b| {
+        b.iter_batched(
+            || allocator.allocate().expect("Failed to allocate PID"),
+            drop,
+            BatchSize::NumIterations(1024),
+        )
+    });
+}
+
+fn sequential_allocation_benchmark(c: &mut Criterion) {
+    let allocator = PidAllocator::<32>::new();
+    let mut pids = Vec::with_capacity(PidAllocator::<32>::CAPACITY);
+    c.bench_function("PidAllocator::allocate (sequential fill)", |b| {
+        b.iter(|| {
+            pids.extend(allocator.allocations());
+            black_box(pids.len());
+            pids.clear();
+        })
+    });
+}
+
+fn sparse_high_benchmark(c: &mut Criterion) {
+    // Every layer is full except the top one, and the hint is pulled back to layer 0 before each
+    // allocation, so the search has to get past 31 full layers to find a free PID.
+    let allocator = PidAllocator::<32>::new();
+    allocator.allocations().for_each(core::mem::forget);
+    allocator.try_recycle(PidAllocator::<32>::CAPACITY - 1);
+    c.bench_function("PidAllocator::allocate (only the top layer free)", |b| {
+        b.iter(|| {
+            allocator.try_recycle(0);
+            core::mem::forget(allocator.allocate_at(0));
+            black_box(allocator.allocate());
+        })
+    });
+}
+
+/// Runs `iters` allocate/recycle cycles split across `THREADS` threads and returns the wall time.
+fn contended<F: Fn() + Sync>(iters: u64, allocate: F) -> Duration {
+    let per_thread = iters.div_ceil(THREADS as u64);
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..per_thread {
+                    allocate();
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn sharded_allocator_benchmark(c: &mut Criterion) {
+    let single = PidAllocator::<32>::new();
+    c.bench_function("PidAllocator::allocate (4 threads)", |b| {
+        b.iter_custom(|iters| contended(iters, || drop(black_box(single.allocate()))))
+    });
+
+    let sharded = ShardedPidAllocator::<THREADS, 8>::new();
+    c.bench_function("ShardedPidAllocator::allocate (4 threads)", |b| {
+        b.iter_custom(|iters| contended(iters, || drop(black_box(sharded.allocate()))))
+    });
+}
+
+fn relax_strategy_benchmark(c: &mut Criterion) {
+    let spin = PidAllocator::<32>::with_relax::<Spin>();
+    c.bench_function("PidAllocator::allocate (4 threads, Spin)", |b| {
+        b.iter_custom(|iters| contended(iters, || drop(black_box(spin.allocate()))))
+    });
+
+    let yielding = PidAllocator::<32>::with_relax::<YieldNow>();
+    c.bench_function("PidAllocator::allocate (4 threads, Yield)", |b| {
+        b.iter_custom(|iters| contended(iters, || drop(black_box(yielding.allocate()))))
+    });
+}
+
+criterion_group!(
+    benches,
+    pid_allocator_benchmark,
+    recycle_benchmark,
+    sequential_allocation_benchmark,
+    sparse_high_benchmark,
+    sharded_allocator_benchmark,
+    relax_strategy_benchmark
+);
 criterion_main!(benches);
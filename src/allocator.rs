@@ -1,16 +1,102 @@
-use core::ops::Deref;
+use core::{
+    borrow::Borrow,
+    cmp, fmt,
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    num::NonZeroUsize,
+    ops::{Deref, Range},
+};
 
-use alloc::sync::Arc;
-use spin::mutex::SpinMutex;
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec, vec::Vec};
+#[cfg(feature = "metrics")]
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "async")]
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+#[cfg(feature = "rand_core")]
+use rand_core::RngCore;
+use spin::mutex::{SpinMutex, SpinMutexGuard};
+
+use crate::{
+    error::PidError,
+    relax::{RelaxStrategy, Spin},
+    word::BitWord,
+};
+
+/// The allocation status of a range of PIDs, as reported by [`PidAllocator::contains_range`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RangeStatus {
+    /// Every PID in the range is allocated.
+    Allocated,
+    /// No PID in the range is allocated.
+    Free,
+    /// Some, but not all, PIDs in the range are allocated.
+    Mixed,
+}
+
+/// The order in which [`PidAllocator::allocate`] picks among the free PIDs.
+///
+/// The policy is chosen with [`PidAllocator::with_policy`] and can be changed later with
+/// [`PidAllocator::set_policy`]. It applies to every allocation that picks a PID on the allocator's
+/// behalf, such as `allocate`, `allocate_n` and `allocations`, and to `peek_next`, but not to
+/// methods that target specific numbers like `allocate_at` or `allocate_in`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AllocationPolicy {
+    /// Hands out the lowest free PID. This is the default and the fastest policy.
+    #[default]
+    LowestFirst,
+    /// Hands out the highest free PID.
+    HighestFirst,
+    /// Hands out the lowest free PID above the previously allocated one, wrapping around at the end,
+    /// like Linux does. Recently recycled PIDs are only reused after a full cycle.
+    RoundRobin,
+    /// Hands out a free PID picked by a small built-in pseudo-random generator.
+    ///
+    /// The sequence is deterministic, and [`PidAllocator::with_seed`] picks its seed. It is not
+    /// suitable where PIDs must be unpredictable; use `PidAllocator::allocate_random` with a proper
+    /// RNG for that.
+    Random,
+}
+
+/// Returns the smallest `ORDER` for which a [`PidAllocator`] with the default `usize` layers can
+/// manage `max_pids` PIDs.
+///
+/// The number of PIDs per layer is `usize::BITS`, which differs between targets, so hard-coding an
+/// order ties the capacity to the pointer width. Being a `const fn`, this can pick the order in the
+/// type itself.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::{required_order, PidAllocator};
+///
+/// let allocator = PidAllocator::<{ required_order(1000) }>::new();
+/// assert!(PidAllocator::<{ required_order(1000) }>::CAPACITY >= 1000);
+/// assert!(allocator.allocate_at(999).is_some());
+/// ```
+pub const fn required_order(max_pids: usize) -> usize {
+    max_pids.div_ceil(usize::BITS as usize)
+}
 
 /// A thread-safe PID allocator that can allocate and recycle PIDs efficiently.
 /// It encapsulates the allocator's state within an `Arc<SpinMutex<...>>` to allow safe shared access across threads.
 /// This design ensures that PIDs can be allocated and recycled from multiple threads without data races or consistency issues.
 ///
 /// The `ORDER` generic parameter determines the capacity of the allocator,
-/// with the total number of allocatable PIDs being `ORDER * usize::BITS`. This makes
+/// with the total number of allocatable PIDs being `ORDER * W::BITS`. This makes
 /// the allocator flexible for various use cases, whether you need a few dozen PIDs or thousands.
 ///
+/// The optional `W` parameter selects the [`BitWord`] used for each bitmap layer and defaults to `usize`.
+/// Picking a fixed-width word such as `u32` makes the capacity and the exported bitmap layout
+/// independent of the target's pointer width, e.g. to match an external ABI.
+///
+/// The optional `R` parameter selects the [`RelaxStrategy`] used while waiting for the lock and
+/// defaults to [`Spin`]. See the [`relax`](crate::relax) module and [`PidAllocator::with_relax`].
+///
 /// # Examples
 ///
 /// Creating a new PID Allocator:
@@ -31,7 +117,7 @@ use spin::mutex::SpinMutex;
 /// //Checking if a PID is allocated (using `contains` method):
 /// let pid = allocator.allocate().expect("Failed to allocate PID");
 /// assert!(allocator.contains(*pid), "The PID should be marked as allocated.");
-/// 
+///
 /// //Recycle a PID by simply dropping it:
 /// {
 ///     let pid = allocator.allocate().expect("Failed to allocate PID");
@@ -45,19 +131,201 @@ use spin::mutex::SpinMutex;
 /// This allocator is designed to be used in environments where PID recycling is frequent,
 /// and thread safety is a concern. Its performance characteristics are optimized for scenarios
 /// where both allocation and deallocation (recycling) of PIDs are common operations.
-#[derive(Debug, Default)]
-pub struct PidAllocator<const ORDER: usize> {
-    inner: Arc<SpinMutex<PidAllocatorInner<ORDER>>>,
+///
+/// Its `Debug` output summarizes occupancy as `PidAllocator { allocated, free, capacity }` rather
+/// than dumping the bitmap; use [`PidAllocator::debug_bitmap`] to see individual PIDs.
+pub struct PidAllocator<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    pub(crate) inner: Arc<Shared<ORDER, W, R>>,
 }
 
-/// The internal state of the PID allocator, containing the layers of available PIDs.
+/// The state shared by an allocator, its clones and its handles: the locked bitmap and, with the
+/// `metrics` feature, the contention counter. Dereferences to the lock.
 #[derive(Debug)]
-pub(crate) struct PidAllocatorInner<const ORDER: usize> {
-    top_layer: usize,
-    bottom_layers: [usize; ORDER],
+pub(crate) struct Shared<const ORDER: usize, W: BitWord, R: RelaxStrategy> {
+    state: SpinMutex<PidAllocatorInner<ORDER, W>, R>,
+    /// The number of allocations that found the lock already held.
+    #[cfg(feature = "metrics")]
+    contention: AtomicU64,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Shared<ORDER, W, R> {
+    fn new(inner: PidAllocatorInner<ORDER, W>) -> Self {
+        Self {
+            state: SpinMutex::new(inner),
+            #[cfg(feature = "metrics")]
+            contention: AtomicU64::new(0),
+        }
+    }
+
+    /// Locks the state for an allocation. With the `metrics` feature, an attempt that finds the
+    /// lock already held is counted as contended before falling back to spinning.
+    pub(crate) fn lock_for_allocation(&self) -> SpinMutexGuard<'_, PidAllocatorInner<ORDER, W>> {
+        #[cfg(feature = "metrics")]
+        {
+            if let Some(inner) = self.state.try_lock() {
+                return inner;
+            }
+            self.contention.fetch_add(1, Ordering::Relaxed);
+        }
+        self.state.lock()
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for Shared<ORDER, W, R> {
+    type Target = SpinMutex<PidAllocatorInner<ORDER, W>, R>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+/// The internal state of the PID allocator, containing the layers of available PIDs.
+pub(crate) struct PidAllocatorInner<const ORDER: usize, W: BitWord = usize> {
+    /// A summary of the bottom layers: bit `i` is set exactly when `bottom_layers[i]` is full.
+    pub(crate) top_layer: usize,
+    bottom_layers: [W; ORDER],
+    /// The lowest layer that may still have a free PID. Every layer below it is full,
+    /// which lets `allocate` skip them when PIDs are handed out sequentially.
+    hint_layer: usize,
+    /// PIDs at or above this bound are never handed out, even if the bitmap has room for them.
+    max: usize,
+    /// Recycled PIDs that are not yet free again, oldest first, each paired with the
+    /// value of `allocations` at the time it was recycled. Their bits stay set meanwhile.
+    quarantine: VecDeque<(usize, usize)>,
+    /// How many allocations a recycled PID must sit out before it becomes free again.
+    quarantine_depth: usize,
+    /// PIDs below this bound are pinned: they are never handed out, and free ones have their bit set.
+    pin_floor: usize,
+    /// The pinned PIDs whose bit is set only because of the pin, not because they are allocated.
+    pinned: Vec<usize>,
+    /// The number of successful allocations so far, wrapping on overflow.
+    allocations: usize,
+    /// The number of times `allocate` found no free PID, wrapping on overflow.
+    failures: usize,
+    /// The order in which `allocate` picks among the free PIDs.
+    policy: AllocationPolicy,
+    /// Where the search for the next PID starts under `RoundRobin`, one past the last allocated PID.
+    cursor: usize,
+    /// The state of the xorshift generator used by the `Random` policy. Never zero.
+    rng_state: u64,
+    /// Called by `recycle` with every recycled PID, if set.
+    recycle_hook: Option<RecycleHook>,
+    /// The sequence number each allocated PID was handed out at, or 0 if unknown. Empty unless
+    /// sequence tracking is enabled, in which case it has one entry per PID.
+    sequences: Vec<u64>,
+    /// The sequence number given to the most recent allocation while tracking is enabled.
+    next_sequence: u64,
+    /// Futures waiting in `allocate_async` for a PID to be recycled, each with its own token, since
+    /// several futures polled by the same task share a waker.
+    #[cfg(feature = "async")]
+    waiters: VecDeque<(u64, Waker)>,
+    /// The token given to the next future that registers in `waiters`.
+    #[cfg(feature = "async")]
+    next_waiter: u64,
 }
 
-impl<const ORDER: usize> PidAllocator<ORDER> {
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PidAllocator<ORDER, W, R> {
+    /// The number of bitmap layers, equal to the `ORDER` parameter.
+    ///
+    /// Together with [`PidAllocator::BITS_PER_LAYER`], this lets generic code and macros recover the
+    /// allocator's layout without re-specifying its parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// type Allocator = PidAllocator<16>;
+    /// assert_eq!(Allocator::ORDER, 16);
+    /// assert_eq!(Allocator::BITS_PER_LAYER, usize::BITS as usize);
+    /// assert_eq!(Allocator::CAPACITY, Allocator::ORDER * Allocator::BITS_PER_LAYER);
+    /// ```
+    pub const ORDER: usize = ORDER;
+
+    /// The number of PIDs tracked by a single layer, equal to `W::BITS`.
+    pub const BITS_PER_LAYER: usize = W::BITS as usize;
+
+    /// The total number of PIDs this allocator can manage, equal to `ORDER * W::BITS`.
+    ///
+    /// The product is computed with a checked multiplication, so an `ORDER` whose capacity does not
+    /// fit in a `usize` fails to compile instead of wrapping. With `ORDER` within the limit checked by
+    /// [`PidAllocator::assert_valid`] this cannot happen, even on 16-bit targets, and since every PID
+    /// is below `CAPACITY`, no PID number computed from a layer index and bit index can overflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// assert_eq!(PidAllocator::<8, u32>::CAPACITY, 8 * 32);
+    /// assert_eq!(PidAllocator::<8>::CAPACITY, 8 * usize::BITS as usize);
+    /// ```
+    pub const CAPACITY: usize = PidAllocatorInner::<ORDER, W>::CAPACITY;
+
+    /// The size in bytes of the allocation bitmap, equal to `ORDER * W::BYTES`.
+    ///
+    /// This is the exact length of the buffer returned by [`PidAllocator::as_bytes`] and expected by
+    /// [`PidAllocator::from_bytes`].
+    pub const BITMAP_BYTES: usize = ORDER * W::BYTES;
+
+    /// Returns the bitmap layout as `(ORDER, BITS_PER_LAYER)`, i.e. the number of layer words and the
+    /// number of PIDs tracked by each word.
+    ///
+    /// Together with [`PidAllocator::BITMAP_BYTES`], this describes the bitmap precisely enough for
+    /// tools that generate memory maps or size buffers for [`PidAllocator::as_bytes`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// assert_eq!(PidAllocator::<8>::layout(), (8, usize::BITS as usize));
+    /// assert_eq!(PidAllocator::<8, u32>::layout(), (8, 32));
+    /// assert_eq!(PidAllocator::<8, u32>::BITMAP_BYTES, 32);
+    /// ```
+    pub const fn layout() -> (usize, usize) {
+        (ORDER, Self::BITS_PER_LAYER)
+    }
+
+    /// Asserts at compile time that `ORDER` is a supported number of layers.
+    ///
+    /// The top layer tracks one bit per bottom layer in a single `usize`, so `ORDER` must not exceed
+    /// `usize::BITS`. An `ORDER` of 0 is accepted and yields an allocator with no PIDs at all, which
+    /// is always both empty and full. This is easy to get wrong when `ORDER` is chosen generically,
+    /// so call this method in a const context to turn a bad choice into a build error:
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// const _: () = PidAllocator::<8>::assert_valid();
+    /// const _: () = PidAllocator::<0>::assert_valid();
+    /// ```
+    ///
+    /// An invalid `ORDER` fails to compile:
+    ///
+    /// ```compile_fail
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// const _: () = PidAllocator::<{ usize::BITS as usize + 1 }>::assert_valid();
+    /// ```
+    ///
+    /// An `ORDER` so large that the capacity would overflow `usize` is rejected as soon as the
+    /// capacity is used:
+    ///
+    /// ```compile_fail
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// const _: usize = PidAllocator::<{ usize::MAX / 4 }, u8>::CAPACITY;
+    /// ```
+    ///
+    /// Called at runtime instead, it panics with the same message.
+    pub const fn assert_valid() {
+        assert!(
+            ORDER <= usize::BITS as usize,
+            "PidAllocator ORDER must not exceed usize::BITS"
+        );
+    }
+
     /// Creates a new instance of the PID allocator.
     ///
     /// This constructor initializes the PID allocator, setting up its internal
@@ -77,11 +345,165 @@ impl<const ORDER: usize> PidAllocator<ORDER> {
     /// a total of `8 * usize::BITS` PIDs, which depends on the architecture
     /// (e.g., 256 PIDs for a 32-bit architecture or 512 PIDs for a 64-bit architecture).
     pub fn new() -> Self {
+        Self::from_inner(PidAllocatorInner::new())
+    }
+
+    /// Creates a new PID allocator that never hands out PIDs at or above `max`.
+    ///
+    /// This caps the live PID range to a runtime bound smaller than [`PidAllocator::CAPACITY`],
+    /// e.g. to match the size of an external table, while keeping `ORDER` generous. Bits at or
+    /// above `max` are treated as permanently unavailable: `allocate` returns `None` once every PID
+    /// below `max` is taken, and `allocate_at` rejects numbers at or above `max`. A `max` larger
+    /// than `CAPACITY` is clamped to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::with_max(3);
+    /// let pids: Vec<_> = allocator.allocations().collect();
+    /// assert_eq!(pids.len(), 3);
+    /// assert!(allocator.allocate_at(5).is_none());
+    /// ```
+    pub fn with_max(max: usize) -> Self {
+        Self::from_inner(PidAllocatorInner::with_max(max))
+    }
+
+    /// Creates a new PID allocator that delays the reuse of recycled PIDs.
+    ///
+    /// A recycled PID first enters a FIFO quarantine and only becomes free again after `depth`
+    /// further allocations have succeeded. This avoids PID-reuse races, where a stale reference
+    /// to an exited process briefly aliases a new process that happened to get the same number.
+    /// While quarantined, a PID is reported as free by `contains` and `allocated_count`, but it
+    /// cannot be allocated, not even by `allocate_at`. A `depth` of zero disables the quarantine.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::with_quarantine(2);
+    /// drop(allocator.allocate());
+    ///
+    /// // PID 0 was just freed, so the next two allocations skip it.
+    /// let first = allocator.allocate().expect("Failed to allocate PID");
+    /// let second = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!((*first, *second), (1, 2));
+    /// assert_eq!(*allocator.allocate().expect("Failed to allocate PID"), 0);
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// If every free PID is quarantined, `allocate` fails, and no further allocation can advance the
+    /// quarantine. Use [`PidAllocator::allocate_or_reuse_freed`] to release the oldest quarantined PID
+    /// early in that case. `peek_next` ignores PIDs that are due to leave the quarantine and may
+    /// therefore differ from the next `allocate` on a quarantined allocator.
+    pub fn with_quarantine(depth: usize) -> Self {
+        let mut inner = PidAllocatorInner::new();
+        inner.quarantine_depth = depth;
+        Self::from_inner(inner)
+    }
+
+    /// Creates a new PID allocator that picks free PIDs in the order given by `policy`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::{AllocationPolicy, PidAllocator};
+    ///
+    /// let allocator = PidAllocator::<1, u8>::with_policy(AllocationPolicy::HighestFirst);
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 7);
+    /// ```
+    pub fn with_policy(policy: AllocationPolicy) -> Self {
+        let mut inner = PidAllocatorInner::new();
+        inner.policy = policy;
+        Self::from_inner(inner)
+    }
+
+    /// Creates a new PID allocator with the [`AllocationPolicy::Random`] policy, whose generator
+    /// starts from `seed`.
+    ///
+    /// Two allocators created with the same seed hand out the same sequence of PIDs when driven
+    /// through the same operations, which makes fuzzing and property tests of code built on this
+    /// crate reproducible. Switching the policy later keeps the generator's state, so returning to
+    /// `Random` continues the same sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let first = PidAllocator::<8>::with_seed(42);
+    /// let second = PidAllocator::<8>::with_seed(42);
+    /// let a: Vec<_> = first.allocations().take(16).map(usize::from).collect();
+    /// let b: Vec<_> = second.allocations().take(16).map(usize::from).collect();
+    /// assert_eq!(a, b);
+    /// ```
+    pub fn with_seed(seed: u64) -> Self {
+        let mut inner = PidAllocatorInner::new();
+        inner.policy = AllocationPolicy::Random;
+        inner.rng_state = PidAllocatorInner::<ORDER, W>::seed(seed);
+        Self::from_inner(inner)
+    }
+
+    /// Creates a new PID allocator that records the order in which PIDs are handed out.
+    ///
+    /// Every allocation is tagged with a strictly increasing sequence number, which
+    /// [`PidAllocator::allocation_seq`] reports for as long as the PID stays allocated. This allows
+    /// age-based policies such as picking the oldest process. Tracking costs 8 bytes per PID of
+    /// [`PidAllocator::CAPACITY`], allocated up front, which is why it is opt-in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::with_allocation_seq();
+    /// let older = allocator.allocate_at(9).expect("Failed to allocate PID");
+    /// let newer = allocator.allocate().expect("Failed to allocate PID");
+    /// assert!(allocator.allocation_seq(*older) < allocator.allocation_seq(*newer));
+    /// ```
+    pub fn with_allocation_seq() -> Self {
+        let mut inner = PidAllocatorInner::new();
+        inner.sequences = vec![0; Self::CAPACITY];
+        Self::from_inner(inner)
+    }
+
+    /// Wraps an already initialized state in a new allocator.
+    pub(crate) fn from_inner(inner: PidAllocatorInner<ORDER, W>) -> Self {
         Self {
-            inner: Arc::new(SpinMutex::new(PidAllocatorInner::new())),
+            inner: Arc::new(Shared::new(inner)),
         }
     }
 
+    /// Locks the internal state for an allocation, counting contention with the `metrics` feature.
+    fn lock_for_allocation(&self) -> SpinMutexGuard<'_, PidAllocatorInner<ORDER, W>> {
+        self.inner.lock_for_allocation()
+    }
+
+    /// Returns how many allocations had to wait for the internal lock because another
+    /// thread was holding it.
+    ///
+    /// This is intended for tuning lock strategy (e.g. deciding whether to switch to a
+    /// [`ShardedPidAllocator`](crate::ShardedPidAllocator)). The counter is only maintained
+    /// with the `metrics` feature enabled, so it adds no overhead by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _pid = allocator.allocate();
+    /// assert_eq!(allocator.contention_count(), 0); // No other thread was competing for the lock.
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn contention_count(&self) -> u64 {
+        self.inner.contention.load(Ordering::Relaxed)
+    }
+
     /// Attempts to allocate a new PID. Returns `Some(Pid)` if successful, or `None` if all PIDs are currently allocated.
     /// The allocated PID is wrapped in a `Pid` object, which will automatically recycle the PID when dropped.
     ///
@@ -123,109 +545,3612 @@ impl<const ORDER: usize> PidAllocator<ORDER> {
     ///
     /// In this example, PIDs are continuously allocated until no more are available,
     /// at which point `allocate()` returns `None`.
-    pub fn allocate(&self) -> Option<Pid<ORDER>> {
-        let mut inner = self.inner.lock();
+    #[inline]
+    pub fn allocate(&self) -> Option<Pid<ORDER, W, R>> {
+        let mut inner = self.lock_for_allocation();
         inner.allocate().map(|number| Pid {
             number,
             allocator: self.inner.clone(),
         })
     }
 
-    /// Checks whether a given PID is currently allocated.
+    /// Attempts to allocate the highest free PID, whatever the allocator's [`AllocationPolicy`].
     ///
-    /// # Parameters
+    /// This is a one-off version of [`AllocationPolicy::HighestFirst`], e.g. for placement strategies
+    /// that fill some PIDs from the top while the rest are allocated bottom-up. Returns `None` if
+    /// all PIDs are already allocated.
     ///
-    /// * `number`: The PID number to check for allocation.
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let pid = allocator.allocate_highest().expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 7);
+    /// assert_eq!(allocator.peek_next(), Some(0));
+    /// ```
+    pub fn allocate_highest(&self) -> Option<Pid<ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate_highest()?;
+        Some(Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate a new PID that is guaranteed to be non-zero.
+    ///
+    /// Many APIs treat PID 0 as a null or invalid sentinel. This method never hands out 0, leaving
+    /// it free for regular [`PidAllocator::allocate`] calls, and returns a [`NonZeroPid`] that
+    /// dereferences to a [`NonZeroUsize`]. Thanks to the niche, `Option<NonZeroPid>` is no larger
+    /// than the handle itself.
     ///
     /// # Returns
     ///
-    /// * `true` if the PID is currently allocated.
-    /// * `false` otherwise.
+    /// * `Some(NonZeroPid<ORDER, W>)` containing the lowest free non-zero PID.
+    /// * `None` if every PID other than 0 is already allocated.
     ///
-    /// # Example
+    /// # Examples
     ///
     /// ```
     /// use pid_allocator::PidAllocator;
     ///
-    /// let allocator = PidAllocator::<32>::new();
-    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate_nonzero().expect("Failed to allocate PID");
+    /// assert_eq!(pid.get(), 1);
+    /// assert!(!allocator.contains(0));
+    /// ```
+    pub fn allocate_nonzero(&self) -> Option<NonZeroPid<ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate_nonzero()?;
+        Some(NonZeroPid {
+            number: NonZeroUsize::new(number)?,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate a new PID whose handle borrows the allocator instead of sharing ownership of it.
+    ///
+    /// A [`Pid`] holds an `Arc` to the allocator's state, so every allocation and every drop performs an
+    /// atomic reference count update. A [`BorrowedPid`] holds a plain `&'a PidAllocator` instead, which
+    /// avoids that cost for short-lived allocations that never outlive the allocator. It recycles the PID
+    /// through the borrow when dropped.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(BorrowedPid<'_, ORDER, W>)` containing the lowest free PID.
+    /// * `None` if all PIDs are already allocated.
+    ///
+    /// # Examples
     ///
-    /// assert!(allocator.contains(*pid), "The PID should be marked as allocated.");
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// {
+    ///     let pid = allocator.allocate_borrowed().expect("Failed to allocate PID");
+    ///     assert!(allocator.contains(*pid));
+    /// }
+    /// assert!(!allocator.contains(0));
     /// ```
     ///
     /// # Note
     ///
-    /// This method performs a read-only operation on the allocator's state and is thread-safe,
-    /// thanks to the internal use of `Arc<SpinMutex<...>>`. However, because the state of the allocator
-    /// can change in concurrent environments, the returned allocation state might not remain valid
-    /// immediately after this method is called.
-    pub fn contains(&self, number: usize) -> bool {
-        self.inner.lock().contains(number)
-    }
-}
-
-impl<const ORDER: usize> PidAllocatorInner<ORDER> {
-    pub(crate) fn new() -> Self {
-        Self {
-            top_layer: 0,
-            bottom_layers: [0; ORDER],
-        }
-    }
-
-    /// Allocates a PID from the internal state. This method should only be called with exclusive access to the state.
-    pub(crate) fn allocate(&mut self) -> Option<usize> {
-        for (index, &layer) in self.bottom_layers.iter().enumerate() {
-            if layer != usize::MAX {
-                let free_bit = (!layer).trailing_zeros() as usize;
-                self.bottom_layers[index] |= 1 << free_bit;
-                if self.bottom_layers[index] == usize::MAX {
-                    self.top_layer |= 1 << index;
-                }
-                return Some(index * (usize::BITS as usize) + free_bit);
-            }
-        }
-        None
+    /// Because the handle borrows the allocator, it cannot be moved to another thread on its own,
+    /// e.g. with `std::thread::spawn`; it can only cross into threads that are scoped to the borrow.
+    /// Use [`PidAllocator::allocate`] for PIDs that must outlive the current scope.
+    pub fn allocate_borrowed(&self) -> Option<BorrowedPid<'_, ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate()?;
+        Some(BorrowedPid {
+            number,
+            allocator: &self.inner,
+        })
     }
 
-    /// Recycles the given PID, making it available for allocation again.
-    pub(crate) fn recycle(&mut self, number: usize) {
-        const BITS_PER_LAYER_SHIFT: usize = usize::BITS.trailing_zeros() as usize;
-
-        let layer_index = number >> BITS_PER_LAYER_SHIFT;
-        let bit_index = number & (usize::BITS - 1) as usize;
-
-        self.bottom_layers[layer_index] &= !(1 << bit_index);
-        self.top_layer &= !(1 << layer_index);
+    /// Reserves the lowest free PID for a two-phase setup, e.g. of a new process.
+    ///
+    /// The PID is allocated right away, so it cannot be handed out twice, but the returned
+    /// [`Reservation`] leaves the outcome open: call [`Reservation::commit`] once setup succeeded to
+    /// keep the PID as a regular [`Pid`], or [`Reservation::abort`] (or drop the reservation, e.g. by
+    /// returning early with `?`) to free it again. Returns `None` if all PIDs are already allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    ///
+    /// let reservation = allocator.reserve().expect("Failed to reserve PID");
+    /// assert!(allocator.contains(*reservation));
+    /// reservation.abort();
+    /// assert!(!allocator.contains(0));
+    ///
+    /// let reservation = allocator.reserve().expect("Failed to reserve PID");
+    /// let pid = reservation.commit();
+    /// assert!(allocator.contains(*pid));
+    /// ```
+    pub fn reserve(&self) -> Option<Reservation<ORDER, W, R>> {
+        self.allocate().map(|pid| Reservation { pid })
     }
 
-    /// Checks whether a given PID is currently allocated.
-    pub(crate) fn contains(&self, number: usize) -> bool {
-        const BITS_PER_LAYER_SHIFT: usize = usize::BITS.trailing_zeros() as usize;
-        let layer_index = number >> BITS_PER_LAYER_SHIFT;
-        let bit_index = number & ((1 << BITS_PER_LAYER_SHIFT) - 1);
-
-        if layer_index < self.bottom_layers.len() {
-            (self.bottom_layers[layer_index] & (1 << bit_index)) != 0
-        } else {
-            false
-        }
+    /// Reserves `n` PIDs at once for admission control, guaranteeing that `n` later allocations
+    /// will succeed, or reserves nothing and returns `None` if fewer than `n` PIDs are free.
+    ///
+    /// Unlike [`PidAllocator::allocate_n`], no handles are created up front: the PIDs are claimed
+    /// under a single lock and held by the returned [`MultiReservation`], which hands them out one
+    /// at a time with [`MultiReservation::draw`]. PIDs that were never drawn are freed under a
+    /// single lock when the reservation is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let mut reservation = allocator.reserve_n(4).expect("Failed to reserve PIDs");
+    /// assert!(allocator.reserve_n(5).is_none(), "Only 4 PIDs are left");
+    ///
+    /// let pid = reservation.draw().expect("4 PIDs are reserved");
+    /// drop(reservation);
+    /// assert_eq!(allocator.allocated_count(), 1);
+    /// assert!(allocator.contains(*pid));
+    /// ```
+    pub fn reserve_n(&self, n: usize) -> Option<MultiReservation<ORDER, W, R>> {
+        let numbers = self.lock_for_allocation().allocate_n(n)?;
+        Some(MultiReservation {
+            allocator: self.clone(),
+            numbers: numbers.into_iter(),
+        })
     }
-}
 
-impl<const ORDER: usize> Default for PidAllocatorInner<ORDER> {
-    fn default() -> Self {
-        Self::new()
-    }
+    /// Attempts to allocate a randomly chosen free PID.
+    ///
+    /// Sequential PIDs leak the order in which processes were created and make PIDs easy to guess.
+    /// This method instead scans from a random starting layer for the first layer with a free PID,
+    /// then picks one of that layer's free PIDs at random. The randomness comes from the supplied
+    /// `rng`, so the crate stays `no_std` and tests can use a seeded generator for reproducible results.
+    ///
+    /// This method is only available with the `rand_core` feature enabled.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Pid<ORDER, W>)` containing a randomly chosen free PID.
+    /// * `None` if all PIDs are already allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    /// use rand_core::{impls, RngCore};
+    ///
+    /// struct Counter(u64);
+    ///
+    /// impl RngCore for Counter {
+    ///     fn next_u32(&mut self) -> u32 {
+    ///         self.next_u64() as u32
+    ///     }
+    ///     fn next_u64(&mut self) -> u64 {
+    ///         self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    ///         self.0 >> 16
+    ///     }
+    ///     fn fill_bytes(&mut self, dest: &mut [u8]) {
+    ///         impls::fill_bytes_via_next(self, dest)
+    ///     }
+    ///     fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+    ///         self.fill_bytes(dest);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate_random(&mut Counter(42)).expect("Failed to allocate PID");
+    /// assert!(allocator.contains(*pid));
+    /// ```
+    #[cfg(feature = "rand_core")]
+    pub fn allocate_random(&self, rng: &mut impl RngCore) -> Option<Pid<ORDER, W, R>> {
+        let mut inner = self.lock_for_allocation();
+        inner.allocate_random(rng).map(|number| Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate a new PID, reusing the oldest quarantined PID early if the pool is otherwise full.
+    ///
+    /// On an allocator created with [`PidAllocator::with_quarantine`], recycled PIDs sit out a number of
+    /// allocations before they become free again, so `allocate` can fail while PIDs are quarantined.
+    /// This method instead pulls the oldest quarantined PID out of the quarantine and hands it out,
+    /// degrading the reuse-delay guarantee under pressure rather than failing. Without a quarantine it
+    /// behaves exactly like [`PidAllocator::allocate`].
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Pid<ORDER, W>)` containing the lowest free PID, or the oldest quarantined PID if none is free.
+    /// * `None` if every PID is allocated and none is quarantined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::with_quarantine(4);
+    /// let mut pids: Vec<_> = allocator.allocations().collect();
+    /// pids.remove(5); // PID 5 enters the quarantine.
+    ///
+    /// assert!(allocator.allocate().is_none());
+    /// let pid = allocator.allocate_or_reuse_freed().expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 5);
+    /// ```
+    pub fn allocate_or_reuse_freed(&self) -> Option<Pid<ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate_or_reuse_freed()?;
+        Some(Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate a new PID without ever waiting for the allocator's lock.
+    ///
+    /// Interrupt handlers and other contexts that must never block cannot call [`PidAllocator::allocate`],
+    /// which spins while another thread holds the lock. This method takes the lock with a single
+    /// `try_lock` and gives up immediately if it is held, reporting that separately from exhaustion
+    /// so the caller can decide whether to retry later.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(Pid<ORDER, W>))` containing the lowest free PID.
+    /// * `Ok(None)` if the lock was free but all PIDs are already allocated.
+    /// * `Err(PidError::WouldBlock)` if another thread held the lock. Nothing was allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator
+    ///     .try_allocate_nonblocking()
+    ///     .expect("The lock is not contended")
+    ///     .expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 0);
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// Dropping the returned handle still takes the lock to recycle the PID. In a context that must not block,
+    /// convert the handle with `usize::from` and recycle the number later from a context that may wait.
+    pub fn try_allocate_nonblocking(&self) -> Result<Option<Pid<ORDER, W, R>>, PidError> {
+        let Some(mut inner) = self.inner.try_lock() else {
+            #[cfg(feature = "metrics")]
+            self.inner.contention.fetch_add(1, Ordering::Relaxed);
+            return Err(PidError::WouldBlock);
+        };
+        Ok(inner.allocate().map(|number| Pid {
+            number,
+            allocator: self.inner.clone(),
+        }))
+    }
+
+    /// Attempts to allocate a new PID without waiting for the lock, reporting why it failed as a
+    /// [`PidError`].
+    ///
+    /// This is [`PidAllocator::try_allocate_nonblocking`] with both failure modes folded into one
+    /// typed error, so callers can match on them together, e.g. to retry on contention but shed load
+    /// when the pool is full. The same note about dropping the handle applies.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Pid<ORDER, W>)` containing the lowest free PID.
+    /// * `Err(PidError::WouldBlock)` if another thread held the lock. Nothing was allocated.
+    /// * `Err(PidError::Exhausted)` if the lock was free but all PIDs are already allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::{PidAllocator, PidError};
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let _pids: Vec<_> = allocator.allocations().collect();
+    /// assert_eq!(allocator.allocate_checked().map(|pid| *pid), Err(PidError::Exhausted));
+    /// ```
+    pub fn allocate_checked(&self) -> Result<Pid<ORDER, W, R>, PidError> {
+        self.try_allocate_nonblocking()?.ok_or(PidError::Exhausted)
+    }
+
+    /// Allocates a new PID, spinning until one becomes available.
+    ///
+    /// This repeatedly calls [`PidAllocator::allocate`], issuing [`core::hint::spin_loop`] between
+    /// attempts, and only returns once a PID has been allocated. It suits bounded worker pools where
+    /// a producer should wait for a free PID instead of handling `None` itself.
+    ///
+    /// # Deadlocks
+    ///
+    /// If the allocator is full and no other thread ever drops a `Pid`, this method never returns.
+    /// In particular, calling it from the only thread that holds the outstanding handles will hang forever.
+    /// Use [`PidAllocator::allocate`] when the caller can handle exhaustion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1>::new();
+    /// let pid = allocator.allocate_blocking();
+    /// assert!(allocator.contains(*pid));
+    /// ```
+    pub fn allocate_blocking(&self) -> Pid<ORDER, W, R> {
+        loop {
+            if let Some(pid) = self.allocate() {
+                return pid;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Attempts to allocate a new PID, retrying a bounded number of times if the pool is full.
+    ///
+    /// This sits between [`PidAllocator::allocate`], which gives up at once, and
+    /// [`PidAllocator::allocate_blocking`], which never gives up. After each failed attempt, `relax`
+    /// is called with the zero-based index of the retry about to be made, so the caller can back off
+    /// exponentially, yield, or sleep between attempts. Returns `None` once the first attempt and
+    /// `max_retries` retries have all failed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let _pids: Vec<_> = allocator.allocations().collect();
+    ///
+    /// let mut waited = 0;
+    /// let pid = allocator.allocate_retry(3, |attempt| {
+    ///     for _ in 0..1 << attempt {
+    ///         core::hint::spin_loop();
+    ///     }
+    ///     waited += 1;
+    /// });
+    /// assert!(pid.is_none());
+    /// assert_eq!(waited, 3);
+    /// ```
+    pub fn allocate_retry(
+        &self,
+        max_retries: usize,
+        mut relax: impl FnMut(usize),
+    ) -> Option<Pid<ORDER, W, R>> {
+        if let Some(pid) = self.allocate() {
+            return Some(pid);
+        }
+        (0..max_retries).find_map(|attempt| {
+            relax(attempt);
+            self.allocate()
+        })
+    }
+
+    /// Allocates a new PID, waiting asynchronously until one becomes available.
+    ///
+    /// If the allocator is full, the calling task registers its `Waker` and is woken once a
+    /// `Pid` is dropped or a PID is otherwise recycled. Each recycle wakes one pending waiter,
+    /// in the order the waiters registered. Unlike [`PidAllocator::allocate_blocking`], this
+    /// never busy-spins, which makes it suitable for async runtimes.
+    ///
+    /// This method is only available with the `async` feature enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # async fn run() {
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1>::new();
+    /// let pid = allocator.allocate_async().await;
+    /// assert!(allocator.contains(*pid));
+    /// # }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn allocate_async(&self) -> Pid<ORDER, W, R> {
+        AllocateAsync {
+            allocator: self,
+            token: None,
+        }
+        .await
+    }
+
+    /// Attempts to allocate a specific PID. Returns `Some(Pid)` if the requested number was free,
+    /// or `None` if it is already allocated or lies outside the allocator's capacity.
+    ///
+    /// # Parameters
+    ///
+    /// * `number`: The PID number to claim.
+    ///
+    /// # Returns
+    ///
+    /// `None` in each of these cases, checked in this order, so that no number can overflow the
+    /// bitmap lookup:
+    ///
+    /// * `number` is at or above [`PidAllocator::capacity`], i.e. beyond [`PidAllocator::CAPACITY`]
+    ///   or the bound set by [`PidAllocator::with_max`], up to and including `usize::MAX`.
+    /// * `number` is already allocated.
+    /// * `number` is quarantined (see [`PidAllocator::with_quarantine`]) or pinned (see
+    ///   [`PidAllocator::pin_low`]). Although such PIDs are not allocated, they are not free either.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate_at(42).expect("PID 42 should be free");
+    /// assert_eq!(*pid, 42);
+    /// assert!(allocator.allocate_at(42).is_none());
+    /// ```
+    pub fn allocate_at(&self, number: usize) -> Option<Pid<ORDER, W, R>> {
+        let mut inner = self.lock_for_allocation();
+        inner.allocate_at(number).then(|| Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate the lowest free PID within `range`.
+    ///
+    /// This lets several subsystems share one allocator while each stays within its own lane
+    /// of PID numbers. The range is clamped to `[0, CAPACITY)`, and to the bound set by
+    /// [`PidAllocator::with_max`] if there is one.
+    ///
+    /// # Parameters
+    ///
+    /// * `range`: The half-open range of PID numbers to allocate from.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Pid<ORDER, W>)` holding the lowest free PID within `range`.
+    /// * `None` if every PID within `range` is already allocated, or the clamped range is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate_in(100..200).expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 100);
+    ///
+    /// assert!(allocator.allocate_in(100..101).is_none());
+    /// ```
+    pub fn allocate_in(&self, range: Range<usize>) -> Option<Pid<ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate_in(range)?;
+        Some(Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate the lowest free PID that is a multiple of `align`, e.g. for hardware
+    /// tables whose slots must sit on a fixed boundary.
+    ///
+    /// Each layer word is masked with the aligned positions, so the search costs one word operation
+    /// per layer, and layers that hold no aligned position are skipped. Returns `None` if every
+    /// aligned PID is allocated. Like [`PidAllocator::allocate_in`], this ignores the
+    /// [`AllocationPolicy`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _first = allocator.allocate().expect("Failed to allocate PID");
+    /// let pid = allocator.allocate_aligned(8).expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 8);
+    /// ```
+    pub fn allocate_aligned(&self, align: usize) -> Option<Pid<ORDER, W, R>> {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        let number = self.lock_for_allocation().allocate_aligned(align)?;
+        Some(Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate the `preferred` PID, falling back to any free PID if the preferred
+    /// one is already allocated or out of range.
+    ///
+    /// This is useful for recurring tasks that would like to get their previous PID back
+    /// whenever it is still available, reducing PID churn.
+    ///
+    /// # Parameters
+    ///
+    /// * `preferred`: The PID number to try first.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(Pid<ORDER>)` holding `preferred` if it was free, or some other free PID otherwise.
+    /// * `None` if all PIDs are already allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let first = allocator.allocate_with_hint(7).expect("Failed to allocate PID");
+    /// assert_eq!(*first, 7);
+    ///
+    /// // PID 7 is taken, so some other free PID is returned instead.
+    /// let second = allocator.allocate_with_hint(7).expect("Failed to allocate PID");
+    /// assert_ne!(*second, 7);
+    /// ```
+    pub fn allocate_with_hint(&self, preferred: usize) -> Option<Pid<ORDER, W, R>> {
+        let mut inner = self.lock_for_allocation();
+        let number = if inner.allocate_at(preferred) {
+            preferred
+        } else {
+            inner.allocate()?
+        };
+        Some(Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Allocates a new PID whose handle runs `on_drop` after the PID has been recycled.
+    ///
+    /// This is useful when freeing a PID should trigger extra cleanup, such as releasing a
+    /// resource associated with it. The closure receives the PID number and is invoked after
+    /// the PID has been returned to the allocator, so it is already free when the closure runs.
+    ///
+    /// # Parameters
+    ///
+    /// * `on_drop`: The closure to invoke with the PID number once it has been recycled.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(PidGuard)` containing the allocated PID if allocation is successful.
+    /// * `None` if all PIDs are already allocated. In that case `on_drop` is dropped without being called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicUsize, Ordering};
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// static FREED: AtomicUsize = AtomicUsize::new(usize::MAX);
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let guard = allocator
+    ///     .allocate_with(|number| FREED.store(number, Ordering::SeqCst))
+    ///     .expect("Failed to allocate PID");
+    /// let number = *guard;
+    ///
+    /// drop(guard);
+    /// assert_eq!(FREED.load(Ordering::SeqCst), number);
+    /// ```
+    pub fn allocate_with<F: FnOnce(usize)>(&self, on_drop: F) -> Option<PidGuard<F, ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate()?;
+        Some(PidGuard {
+            number,
+            allocator: self.inner.clone(),
+            on_drop: Some(on_drop),
+        })
+    }
+
+    /// Allocates the next free PID only if `pred` accepts its number, deciding atomically.
+    ///
+    /// While holding the lock, this picks the PID that [`PidAllocator::allocate`] would hand out and
+    /// passes its number to `pred`. If `pred` returns `true`, the PID is allocated; otherwise it stays
+    /// free and `None` is returned. Since no other thread can allocate in between, there is no race
+    /// between checking a number and claiming it. Returns `None` as well if the allocator is full.
+    ///
+    /// # Note
+    ///
+    /// `pred` runs while the lock is held, so it should be short and must not call back into this
+    /// allocator, or it will deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// assert!(allocator.allocate_if(|number| number != 0).is_none());
+    /// assert!(!allocator.contains(0), "A vetoed PID stays free");
+    ///
+    /// let pid = allocator.allocate_if(|number| number % 2 == 0).expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 0);
+    /// ```
+    pub fn allocate_if(&self, pred: impl FnOnce(usize) -> bool) -> Option<Pid<ORDER, W, R>> {
+        let number = self.lock_for_allocation().allocate_if(pred)?;
+        Some(Pid {
+            number,
+            allocator: self.inner.clone(),
+        })
+    }
+
+    /// Attempts to allocate `n` PIDs at once, all or nothing.
+    ///
+    /// The whole batch is claimed under a single lock. If the allocator runs out of PIDs part-way
+    /// through, every PID already claimed by this call is rolled back before the lock is released,
+    /// leaving the allocator exactly as it was before the call: a failed batch does not advance the
+    /// allocation counters, the [`AllocationPolicy`]'s search state or the quarantine. It only counts
+    /// as one of the [`PidAllocator::failed_allocations`].
+    ///
+    /// # Parameters
+    ///
+    /// * `n` - The number of PIDs to allocate.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(PidBatch<ORDER, W>)` containing `n` handles, in allocation order.
+    /// * `None` if fewer than `n` PIDs are free, in which case nothing is allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let pids = allocator.allocate_n(3).expect("Failed to allocate PIDs");
+    /// assert_eq!(pids.len(), 3);
+    ///
+    /// assert!(allocator.allocate_n(6).is_none());
+    /// assert_eq!(allocator.allocated_count(), 3);
+    /// ```
+    pub fn allocate_n(&self, n: usize) -> Option<PidBatch<ORDER, W, R>> {
+        let numbers = self.lock_for_allocation().allocate_n(n)?;
+        let pids = numbers
+            .into_iter()
+            .map(|number| Pid {
+                number,
+                allocator: self.inner.clone(),
+            })
+            .collect();
+        Some(PidBatch { pids })
+    }
+
+    /// Attempts to allocate `n` consecutive PIDs at once, all or nothing, e.g. for the slots of a
+    /// DMA descriptor ring.
+    ///
+    /// The lowest run of `n` free PIDs below the allocator's bound is claimed under a single lock,
+    /// ignoring the [`AllocationPolicy`]. Each PID gets its own handle, so the PIDs were adjacent when
+    /// allocated but their lifetimes are independent afterwards: dropping one handle frees only that
+    /// PID. Quarantined and pinned PIDs are not free and break up a run.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(PidBatch<ORDER, W>)` containing `n` handles with consecutive numbers, in ascending order.
+    /// * `None` if no run of `n` consecutive free PIDs exists, in which case nothing is allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let _gap = allocator.allocate_at(2).expect("Failed to allocate PID");
+    ///
+    /// let ring = allocator.allocate_batch_contiguous(4).expect("Failed to allocate PIDs");
+    /// assert_eq!(ring.numbers().collect::<Vec<_>>(), [3, 4, 5, 6]);
+    /// assert!(allocator.allocate_batch_contiguous(2).is_some(), "PIDs 0 and 1 are still free");
+    /// ```
+    pub fn allocate_batch_contiguous(&self, n: usize) -> Option<PidBatch<ORDER, W, R>> {
+        let start = self.lock_for_allocation().allocate_contiguous(n)?;
+        let pids = (start..start + n)
+            .map(|number| Pid {
+                number,
+                allocator: self.inner.clone(),
+            })
+            .collect();
+        Some(PidBatch { pids })
+    }
+
+    /// Fills `out` with newly allocated PIDs and returns how many were allocated.
+    ///
+    /// Unlike [`PidAllocator::allocate_n`], this never touches the heap and does not fail as a whole:
+    /// it allocates up to `out.len()` PIDs under a single lock, writes them to the front of `out` and
+    /// leaves the remaining slots `None` once the allocator runs dry. Handles already stored in `out`
+    /// are dropped, and thus recycled, before allocating.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let _taken = allocator.allocate_n(6).expect("Failed to allocate PIDs");
+    ///
+    /// let mut buffer: [_; 4] = Default::default();
+    /// assert_eq!(allocator.allocate_into(&mut buffer), 2);
+    /// assert_eq!(buffer.iter().flatten().map(|pid| **pid).collect::<Vec<_>>(), [6, 7]);
+    /// assert!(buffer[2].is_none() && buffer[3].is_none());
+    /// ```
+    pub fn allocate_into(&self, out: &mut [Option<Pid<ORDER, W, R>>]) -> usize {
+        // Dropping a handle takes the lock, so clear the buffer before locking.
+        out.iter_mut().for_each(|slot| *slot = None);
+
+        let mut inner = self.lock_for_allocation();
+        let mut count = 0;
+        for slot in out.iter_mut() {
+            let Some(number) = inner.allocate() else {
+                break;
+            };
+            *slot = Some(Pid {
+                number,
+                allocator: self.inner.clone(),
+            });
+            count += 1;
+        }
+        count
+    }
+
+    /// Returns an iterator that allocates a new PID on each call to `next`, stopping once the
+    /// allocator is exhausted.
+    ///
+    /// This is convenient for bulk setup and tests that need to fill the pool. Keep the yielded
+    /// handles alive: a dropped `Pid` is recycled immediately, so adaptors that discard items
+    /// (such as `count` or `last`) will keep allocating the same PID and never terminate.
+    ///
+    /// The iterator is fused: once it has returned `None`, it keeps returning `None` even if other
+    /// threads recycle PIDs afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<2>::new();
+    /// let pids: Vec<_> = allocator.allocations().collect();
+    /// assert_eq!(pids.len(), PidAllocator::<2>::CAPACITY);
+    /// assert!(allocator.allocate().is_none());
+    /// ```
+    pub fn allocations(&self) -> Allocations<'_, ORDER, W, R> {
+        Allocations {
+            allocator: self,
+            exhausted: false,
+            descending: false,
+        }
+    }
+
+    /// Returns an iterator that allocates the highest free PID on each call to `next`, stopping
+    /// once the allocator is exhausted.
+    ///
+    /// This is the top-down counterpart of [`PidAllocator::allocations`], built on
+    /// [`PidAllocator::allocate_highest`], and the same caveats about keeping the handles alive apply.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let pids: Vec<_> = allocator.allocations_desc().take(3).collect();
+    /// assert_eq!(pids.iter().map(|pid| **pid).collect::<Vec<_>>(), [7, 6, 5]);
+    /// ```
+    pub fn allocations_desc(&self) -> Allocations<'_, ORDER, W, R> {
+        Allocations {
+            allocator: self,
+            exhausted: false,
+            descending: true,
+        }
+    }
+
+    /// Locks the allocator and returns a guard for performing several operations atomically, e.g.
+    /// checking whether a PID is free, allocating it and recycling another one in a single step.
+    ///
+    /// See [`PidAllocatorGuard`] for the operations available and, in particular, its deadlock
+    /// rules: the lock is not re-entrant, so the allocator must not be used directly, nor its `Pid`s
+    /// dropped, by the same thread while the guard is alive. For the same reason, the guard
+    /// allocates plain numbers instead of `Pid` handles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let mut guard = allocator.lock();
+    /// let number = if guard.allocate_at(7) { 7 } else { guard.allocate().unwrap() };
+    /// assert_eq!(guard.allocated_count(), 1);
+    /// drop(guard);
+    ///
+    /// assert_eq!(number, 7);
+    /// assert!(allocator.try_recycle(number));
+    /// ```
+    pub fn lock(&self) -> PidAllocatorGuard<'_, ORDER, W, R> {
+        PidAllocatorGuard {
+            inner: Some(self.lock_for_allocation()),
+            _allocator: PhantomData,
+            freed: 0,
+        }
+    }
+
+    /// Returns the PID that the next call to [`PidAllocator::allocate`] would hand out, without allocating it.
+    ///
+    /// This uses the same search as `allocate`, so it returns the free PID chosen by the allocator's
+    /// [`AllocationPolicy`], or `None` if the allocator is full.
+    ///
+    /// # Note
+    ///
+    /// The result is advisory only. Another thread may allocate or recycle PIDs as soon as this
+    /// method releases the lock, so the next `allocate` call is not guaranteed to return the same number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let next = allocator.peek_next();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(next, Some(*pid));
+    /// ```
+    pub fn peek_next(&self) -> Option<usize> {
+        self.inner.lock().peek_next()
+    }
+
+    /// Installs a hook that is called with the number of every PID recycled into this allocator.
+    ///
+    /// This allows auditing PID lifetimes without wrapping each handle. The hook sees every recycle,
+    /// whether it comes from dropping a handle or from [`PidAllocator::try_recycle`] and similar methods,
+    /// in the order the PIDs are recycled. Installing a new hook replaces the previous one. Without a
+    /// hook, recycling costs a single extra branch.
+    ///
+    /// # Note
+    ///
+    /// The hook runs while the allocator's lock is held, so it should be short and must not call back
+    /// into this allocator, or it will deadlock. Allocators produced by [`PidAllocator::fork`] start
+    /// without a hook.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicUsize, Ordering};
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// static LAST_RECYCLED: AtomicUsize = AtomicUsize::new(usize::MAX);
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// allocator.set_recycle_hook(|number| LAST_RECYCLED.store(number, Ordering::SeqCst));
+    ///
+    /// drop(allocator.allocate_at(5));
+    /// assert_eq!(LAST_RECYCLED.load(Ordering::SeqCst), 5);
+    /// ```
+    pub fn set_recycle_hook(&self, hook: impl Fn(usize) + Send + Sync + 'static) {
+        self.inner.lock().recycle_hook = Some(RecycleHook(Box::new(hook)));
+    }
+
+    /// Returns the policy that `allocate` currently uses to pick among the free PIDs.
+    pub fn policy(&self) -> AllocationPolicy {
+        self.inner.lock().policy
+    }
+
+    /// Changes the order in which `allocate` picks among the free PIDs from now on.
+    ///
+    /// Already allocated PIDs are unaffected. Switching to [`AllocationPolicy::RoundRobin`] continues
+    /// from the PID after the last one allocated under that policy, or from 0 if it was never used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::{AllocationPolicy, PidAllocator};
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let first = allocator.allocate().expect("Failed to allocate PID");
+    /// drop(first);
+    ///
+    /// allocator.set_policy(AllocationPolicy::RoundRobin);
+    /// let _a = allocator.allocate().expect("Failed to allocate PID");
+    /// let b = allocator.allocate().expect("Failed to allocate PID");
+    /// drop(b);
+    /// // PID 1 was just recycled, but round-robin moves on instead of reusing it.
+    /// assert_eq!(*allocator.allocate().expect("Failed to allocate PID"), 2);
+    /// ```
+    pub fn set_policy(&self, policy: AllocationPolicy) {
+        self.inner.lock().policy = policy;
+    }
+
+    /// Checks whether a given PID is currently allocated.
+    ///
+    /// # Parameters
+    ///
+    /// * `number`: The PID number to check for allocation.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the PID is currently allocated.
+    /// * `false` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<32>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    ///
+    /// assert!(allocator.contains(*pid), "The PID should be marked as allocated.");
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// This method performs a read-only operation on the allocator's state and is thread-safe,
+    /// thanks to the internal use of `Arc<SpinMutex<...>>`. However, because the state of the allocator
+    /// can change in concurrent environments, the returned allocation state might not remain valid
+    /// immediately after this method is called.
+    pub fn contains(&self, number: usize) -> bool {
+        self.inner.lock().contains(number)
+    }
+
+    /// Checks whether the PID held by `pid` is currently allocated.
+    ///
+    /// This is [`PidAllocator::contains`] for a handle, reading the number straight from it so no
+    /// stray integer can be passed by mistake. A live handle from this allocator always reports
+    /// `true`; for a handle from another allocator, the same number is looked up here.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<32>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert!(allocator.contains_pid(&pid));
+    /// ```
+    pub fn contains_pid(&self, pid: &Pid<ORDER, W, R>) -> bool {
+        self.contains(pid.number)
+    }
+
+    /// Returns the number of PIDs that are currently allocated.
+    ///
+    /// This is a point-in-time value computed with a population count over the bitmap, so it is
+    /// cheap even for large `ORDER`. Other threads may allocate or recycle PIDs immediately after
+    /// the lock is released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let first = allocator.allocate().expect("Failed to allocate PID");
+    /// let _second = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(allocator.allocated_count(), 2);
+    ///
+    /// drop(first);
+    /// assert_eq!(allocator.allocated_count(), 1);
+    /// ```
+    pub fn allocated_count(&self) -> usize {
+        self.inner.lock().allocated_count()
+    }
+
+    /// Returns the total number of successful allocations over the allocator's lifetime.
+    ///
+    /// Unlike [`PidAllocator::allocated_count`], which is a point-in-time value, this counter only ever
+    /// grows: recycling a PID does not decrease it. It counts every PID handed out, by any allocation
+    /// method, and wraps around on overflow. PIDs imported through [`PidAllocator::from_bytes`],
+    /// [`PidAllocator::merge`] or [`Extend`] are not allocations and are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// drop(allocator.allocate());
+    /// let _pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(allocator.total_allocations(), 2);
+    /// assert_eq!(allocator.allocated_count(), 1);
+    /// ```
+    pub fn total_allocations(&self) -> usize {
+        self.inner.lock().allocations
+    }
+
+    /// Returns the sequence number at which an allocated PID was handed out.
+    ///
+    /// Sequence numbers start at 1 and strictly increase with every allocation, so comparing them
+    /// orders live PIDs by age. Returns `None` if the PID is not currently allocated, if it was marked
+    /// without being allocated (e.g. through [`PidAllocator::from_bytes`] or [`Extend`]), or if the
+    /// allocator was not created with [`PidAllocator::with_allocation_seq`].
+    pub fn allocation_seq(&self, number: usize) -> Option<u64> {
+        self.inner.lock().allocation_seq(number)
+    }
+
+    /// Returns the total number of allocation attempts that found no free PID over the allocator's lifetime.
+    ///
+    /// This counts failed attempts to allocate the next free PID, e.g. by [`PidAllocator::allocate`]
+    /// or [`PidAllocator::allocate_n`], including every retry of [`PidAllocator::allocate_blocking`] while
+    /// it waits. Requests for specific numbers, such as [`PidAllocator::allocate_at`], are not counted.
+    /// The counter wraps around on overflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let _pids = allocator.allocate_n(8).expect("Failed to allocate PIDs");
+    /// assert!(allocator.allocate().is_none());
+    /// assert_eq!(allocator.failed_allocations(), 1);
+    /// ```
+    pub fn failed_allocations(&self) -> usize {
+        self.inner.lock().failures
+    }
+
+    /// Returns the number of PIDs this allocator can hand out.
+    ///
+    /// This is [`PidAllocator::CAPACITY`], or the lower bound passed to [`PidAllocator::with_max`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// assert_eq!(PidAllocator::<8>::new().capacity(), PidAllocator::<8>::CAPACITY);
+    /// assert_eq!(PidAllocator::<8>::with_max(100).capacity(), 100);
+    /// assert_eq!(PidAllocator::<0>::new().capacity(), 0);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.inner.lock().max
+    }
+
+    /// Returns the number of currently allocated PIDs, following the naming of the standard collections.
+    ///
+    /// This is the same as [`PidAllocator::allocated_count`] and pairs with
+    /// [`PidAllocator::is_empty`] and [`PidAllocator::capacity`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(allocator.len(), 1);
+    /// assert!(!allocator.is_empty());
+    /// ```
+    pub fn len(&self) -> usize {
+        self.allocated_count()
+    }
+
+    /// Returns `true` if no PID is currently allocated.
+    pub fn is_empty(&self) -> bool {
+        self.allocated_count() == 0
+    }
+
+    /// Returns `true` if [`PidAllocator::allocate`] can currently not hand out any PID.
+    ///
+    /// PIDs waiting in a quarantine (see [`PidAllocator::with_quarantine`]) are not available, so an
+    /// allocator can be full while [`PidAllocator::allocated_count`] is below its capacity. An allocator
+    /// with a capacity of 0, e.g. one with `ORDER = 0`, is both empty and full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// let pids = allocator.allocate_n(8).expect("Failed to allocate PIDs");
+    /// assert!(allocator.is_full());
+    /// drop(pids);
+    /// assert!(!allocator.is_full() && allocator.is_empty());
+    /// ```
+    pub fn is_full(&self) -> bool {
+        self.inner.lock().is_full()
+    }
+
+    /// Returns the lowest PID that is currently allocated, or `None` if none is.
+    ///
+    /// The bitmap is scanned a word at a time from the bottom using trailing-zero counts. Quarantined
+    /// and pinned PIDs are not allocated and are skipped, matching [`PidAllocator::contains`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// assert_eq!(allocator.min_allocated(), None);
+    ///
+    /// let _pids = [allocator.allocate_at(70), allocator.allocate_at(9)];
+    /// assert_eq!(allocator.min_allocated(), Some(9));
+    /// ```
+    pub fn min_allocated(&self) -> Option<usize> {
+        self.inner.lock().min_allocated()
+    }
+
+    /// Returns the highest PID that is currently allocated, or `None` if none is.
+    ///
+    /// The bitmap is scanned a word at a time from the top using leading-zero counts. Quarantined
+    /// and pinned PIDs are skipped, as with [`PidAllocator::min_allocated`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// assert_eq!(allocator.max_allocated(), None);
+    ///
+    /// let _pids = [allocator.allocate_at(70), allocator.allocate_at(9)];
+    /// assert_eq!(allocator.max_allocated(), Some(70));
+    /// ```
+    pub fn max_allocated(&self) -> Option<usize> {
+        self.inner.lock().max_allocated()
+    }
+
+    /// Returns the length of the longest run of consecutive free PIDs, across layer boundaries.
+    ///
+    /// This tells whether a block of consecutive PIDs could still be carved out of the allocator.
+    /// An empty allocator reports its full capacity, or the bound set by [`PidAllocator::with_max`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// assert_eq!(allocator.longest_free_run(), PidAllocator::<8>::CAPACITY);
+    ///
+    /// let _pid = allocator.allocate_at(100).expect("Failed to allocate PID");
+    /// assert_eq!(allocator.longest_free_run(), PidAllocator::<8>::CAPACITY - 101);
+    /// ```
+    pub fn longest_free_run(&self) -> usize {
+        self.inner.lock().free_run_stats().0
+    }
+
+    /// Returns how fragmented the free PIDs are, from `0.0` to just below `1.0`.
+    ///
+    /// This is `1 - longest_free_run / free`, where `free` is the number of free PIDs. A value of `0.0`
+    /// means every free PID lies in a single run (or none is free), while values close to `1.0` mean the
+    /// free PIDs are scattered in short runs between allocated ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<1, u8>::new();
+    /// assert_eq!(allocator.fragmentation(), 0.0);
+    ///
+    /// let _pid = allocator.allocate_at(3).expect("Failed to allocate PID");
+    /// // Free runs are 0..3 and 4..8, so the longest holds 4 of the 7 free PIDs.
+    /// assert_eq!(allocator.fragmentation(), 1.0 - 4.0 / 7.0);
+    /// ```
+    pub fn fragmentation(&self) -> f32 {
+        let (longest, free) = self.inner.lock().free_run_stats();
+        if free == 0 {
+            0.0
+        } else {
+            1.0 - longest as f32 / free as f32
+        }
+    }
+
+    /// Checks whether a given PID is currently allocated, rejecting numbers outside the allocator's capacity.
+    ///
+    /// Unlike [`PidAllocator::contains`], which returns `false` for out-of-range numbers,
+    /// this surfaces them as an error so that callers passing the wrong value notice.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` if the PID is currently allocated.
+    /// * `Ok(false)` if the PID is free.
+    /// * `Err(PidError::OutOfRange(number))` if `number >= CAPACITY`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::{PidAllocator, PidError};
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    ///
+    /// assert_eq!(allocator.contains_checked(*pid), Ok(true));
+    /// assert_eq!(allocator.contains_checked(*pid + 1), Ok(false));
+    /// assert_eq!(
+    ///     allocator.contains_checked(PidAllocator::<8>::CAPACITY),
+    ///     Err(PidError::OutOfRange(PidAllocator::<8>::CAPACITY))
+    /// );
+    /// ```
+    pub fn contains_checked(&self, number: usize) -> Result<bool, PidError> {
+        if number >= Self::CAPACITY {
+            return Err(PidError::OutOfRange(number));
+        }
+        Ok(self.contains(number))
+    }
+
+    /// Reports whether every PID in `range` is allocated, every PID is free, or a mix of both.
+    ///
+    /// The whole range is inspected under a single lock, word by word, so the answer is consistent even
+    /// while other threads allocate and recycle. PIDs beyond [`PidAllocator::CAPACITY`] count as free,
+    /// as do quarantined ones, matching [`PidAllocator::contains`]. An empty range is reported as
+    /// [`RangeStatus::Free`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::{PidAllocator, RangeStatus};
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _block = allocator.allocate_n(4).expect("Failed to allocate PIDs");
+    ///
+    /// assert_eq!(allocator.contains_range(0..4), RangeStatus::Allocated);
+    /// assert_eq!(allocator.contains_range(4..8), RangeStatus::Free);
+    /// assert_eq!(allocator.contains_range(2..6), RangeStatus::Mixed);
+    /// ```
+    pub fn contains_range(&self, range: Range<usize>) -> RangeStatus {
+        self.inner.lock().range_status(range)
+    }
+
+    /// Returns how many PIDs in `range` are currently free, e.g. to balance load across partitions
+    /// of the PID space.
+    ///
+    /// The whole range is inspected under a single lock with a population count over the masked
+    /// layer words. Only PIDs that could actually be allocated are counted: quarantined and pinned
+    /// PIDs are not free, and the range is clamped to [`PidAllocator::CAPACITY`] and to the bound
+    /// set by [`PidAllocator::with_max`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _block = allocator.allocate_n(4).expect("Failed to allocate PIDs");
+    ///
+    /// assert_eq!(allocator.free_in_range(0..10), 6);
+    /// assert_eq!(allocator.free_in_range(0..usize::MAX), PidAllocator::<8>::CAPACITY - 4);
+    /// ```
+    pub fn free_in_range(&self, range: Range<usize>) -> usize {
+        self.inner.lock().free_in(range)
+    }
+
+    /// Recycles a PID by number, reporting whether it was actually freed.
+    ///
+    /// This is intended for PIDs whose lifecycle is managed manually (for example PIDs restored
+    /// through [`PidAllocator::from_bytes`]), and for detecting double frees while debugging.
+    /// Recycling a number that is still held by a live `Pid` handle frees it early, and the handle
+    /// will free it again when dropped, so avoid mixing the two.
+    ///
+    /// # Parameters
+    ///
+    /// * `number`: The PID number to recycle.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if the PID was allocated and is now free.
+    /// * `false` if the PID was already free or lies outside the allocator's capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// let number = *pid;
+    /// core::mem::forget(pid); // Take over the PID's lifecycle manually.
+    ///
+    /// assert!(allocator.try_recycle(number));
+    /// assert!(!allocator.try_recycle(number), "A PID can only be freed once");
+    /// assert!(!allocator.try_recycle(usize::MAX));
+    /// ```
+    pub fn try_recycle(&self, number: usize) -> bool {
+        let mut inner = self.inner.lock();
+        let freed = inner.try_recycle(number);
+        if freed {
+            wake_waiter(inner);
+        }
+        freed
+    }
+
+    /// Recycles a collection of PID numbers under a single lock.
+    ///
+    /// This is cheaper than recycling each number individually when tearing down a group of
+    /// related PIDs whose lifecycle is managed manually. Numbers that are out of range or already
+    /// free are skipped, just like [`PidAllocator::try_recycle`] would.
+    ///
+    /// # Parameters
+    ///
+    /// * `numbers`: The PID numbers to recycle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let numbers: Vec<usize> = (0..4)
+    ///     .map(|_| {
+    ///         let pid = allocator.allocate().expect("Failed to allocate PID");
+    ///         let number = *pid;
+    ///         core::mem::forget(pid); // Take over the PID's lifecycle manually.
+    ///         number
+    ///     })
+    ///     .collect();
+    ///
+    /// allocator.recycle_all(numbers.iter().copied().chain([usize::MAX]));
+    /// assert!(numbers.iter().all(|&number| !allocator.contains(number)));
+    /// ```
+    pub fn recycle_all(&self, numbers: impl IntoIterator<Item = usize>) {
+        let mut inner = self.inner.lock();
+        let freed = numbers
+            .into_iter()
+            .filter(|&number| inner.try_recycle(number))
+            .count();
+        wake_waiters(inner, freed);
+    }
+
+    /// Frees every allocated PID under a single lock and returns the numbers that were allocated.
+    ///
+    /// This is a reset and an enumeration in one atomic step, e.g. to notify the owners of all live
+    /// PIDs during shutdown. The numbers are returned in ascending order. Quarantined PIDs are not part
+    /// of the result, but leave the quarantine and become free as well. The recycle hook (see
+    /// [`PidAllocator::set_recycle_hook`]) is not called for drained PIDs.
+    ///
+    /// # Note
+    ///
+    /// Handles that are still alive will free their PID again when dropped, just as with
+    /// [`PidAllocator::try_recycle`], so forget them or make sure they are dropped first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// for number in [4, 9, 2] {
+    ///     core::mem::forget(allocator.allocate_at(number).expect("Failed to allocate PID"));
+    /// }
+    ///
+    /// assert_eq!(allocator.drain_allocated(), [2, 4, 9]);
+    /// assert_eq!(allocator.allocated_count(), 0);
+    /// ```
+    pub fn drain_allocated(&self) -> Vec<usize> {
+        let mut inner = self.inner.lock();
+        let drained = inner.drain();
+        wake_waiters(inner, drained.len());
+        drained
+    }
+
+    /// Frees every allocated PID for which `keep` returns `false`, under a single lock, e.g. for a
+    /// garbage-collection sweep.
+    ///
+    /// `keep` is called once for each allocated PID, in ascending order, and the rejected ones are
+    /// recycled as with [`PidAllocator::try_recycle`], going through the quarantine and the recycle
+    /// hook if configured. Quarantined and pinned PIDs are not allocated and are not passed to `keep`.
+    ///
+    /// # Note
+    ///
+    /// `keep` runs while the lock is held, so it must not use this allocator or drop its `Pid`s.
+    /// Handles still alive for freed PIDs will free their PID again when dropped, just as with
+    /// [`PidAllocator::try_recycle`], so this is meant for PIDs whose lifecycle is managed manually.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let mut allocator = PidAllocator::<8>::new();
+    /// allocator.extend(0..10);
+    ///
+    /// allocator.retain(|number| number < 4);
+    /// assert_eq!(allocator.drain_allocated(), [0, 1, 2, 3]);
+    /// ```
+    pub fn retain(&self, keep: impl FnMut(usize) -> bool) {
+        let mut inner = self.inner.lock();
+        let freed = inner.retain(keep);
+        wake_waiters(inner, freed);
+    }
+
+    /// Frees every PID in `range` under a single lock, e.g. when tearing down a subsystem that owns a
+    /// known window of PIDs.
+    ///
+    /// This clears the bitmap a word at a time, which is much cheaper than recycling each number.
+    /// The range is clamped to [`PidAllocator::CAPACITY`], and quarantined PIDs inside it are freed
+    /// immediately. PIDs outside the range are left untouched. The recycle hook (see
+    /// [`PidAllocator::set_recycle_hook`]) is not called for cleared PIDs.
+    ///
+    /// # Note
+    ///
+    /// Handles still alive for PIDs in the range will free their PID again when dropped, just as with
+    /// [`PidAllocator::try_recycle`], so this is meant for PIDs whose lifecycle is managed manually.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let mut allocator = PidAllocator::<8>::new();
+    /// allocator.extend(0..100);
+    ///
+    /// allocator.clear_range(10..20);
+    /// assert_eq!(allocator.allocated_count(), 90);
+    /// assert!(allocator.contains(9) && !allocator.contains(10) && allocator.contains(20));
+    /// ```
+    pub fn clear_range(&self, range: Range<usize>) {
+        let mut inner = self.inner.lock();
+        let freed = inner.clear_range(range);
+        wake_waiters(inner, freed);
+    }
+
+    /// Makes the lowest `n` PIDs unavailable until [`PidAllocator::unpin_low`] is called, e.g. to
+    /// keep a low range out of circulation during maintenance.
+    ///
+    /// Free PIDs in the range can no longer be allocated, not even by `allocate_at`. PIDs in the range
+    /// that are currently allocated stay allocated, and once recycled they stay unavailable as well.
+    /// Like quarantined PIDs, pinned ones are reported as free by `contains` and `allocated_count`.
+    /// `n` is clamped to the capacity, and a new pin replaces the previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// allocator.pin_low(4);
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 4);
+    /// assert_eq!(allocator.allocated_count(), 1);
+    ///
+    /// allocator.unpin_low();
+    /// assert_eq!(allocator.peek_next(), Some(0));
+    /// ```
+    pub fn pin_low(&self, n: usize) {
+        self.inner.lock().pin_low(n);
+    }
+
+    /// Lifts the pin set by [`PidAllocator::pin_low`], making the pinned PIDs that are not allocated
+    /// available again. Does nothing if no PIDs are pinned.
+    pub fn unpin_low(&self) {
+        let mut inner = self.inner.lock();
+        let freed = inner.unpin_low();
+        wake_waiters(inner, freed);
+    }
+
+    /// Moves a PID from this allocator to `dest`, keeping its number.
+    ///
+    /// Both allocators are locked together, so the number is claimed in `dest` and freed here in one
+    /// step: no other thread can observe the PID as allocated in both or in neither. Returns the new
+    /// handle, owned by `dest`, on success. Returns the original handle unchanged, still allocated here,
+    /// if `dest` already has that number allocated or quarantined, if the number is beyond the bound of
+    /// `dest`, or if `pid` was not allocated from this allocator. Transferring to the same pool is a no-op.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let source = PidAllocator::<8>::new();
+    /// let dest = PidAllocator::<8>::new();
+    /// let pid = source.allocate_at(42).expect("Failed to allocate PID");
+    ///
+    /// let moved = source.transfer(pid, &dest).expect("PID 42 is free in dest");
+    /// assert_eq!(*moved, 42);
+    /// assert!(dest.contains(42) && !source.contains(42));
+    /// ```
+    pub fn transfer(
+        &self,
+        pid: Pid<ORDER, W, R>,
+        dest: &PidAllocator<ORDER, W, R>,
+    ) -> Result<Pid<ORDER, W, R>, Pid<ORDER, W, R>> {
+        if !Arc::ptr_eq(&pid.allocator, &self.inner) {
+            return Err(pid);
+        }
+        if Arc::ptr_eq(&self.inner, &dest.inner) {
+            return Ok(pid);
+        }
+
+        // Lock in address order, so that concurrent transfers in opposite directions cannot deadlock.
+        let (mut source, mut target) = if Arc::as_ptr(&self.inner) < Arc::as_ptr(&dest.inner) {
+            let source = self.inner.lock();
+            (source, dest.inner.lock())
+        } else {
+            let target = dest.inner.lock();
+            (self.inner.lock(), target)
+        };
+        if !target.allocate_at(pid.number) {
+            return Err(pid);
+        }
+        source.recycle(pid.number);
+        drop(target);
+        wake_waiter(source);
+
+        let number = usize::from(pid);
+        Ok(Pid {
+            number,
+            allocator: dest.inner.clone(),
+        })
+    }
+
+    /// Creates an independent deep copy of the allocator's current state.
+    ///
+    /// The fork starts out with exactly the same PIDs allocated, but from then on the two allocators
+    /// evolve separately: allocating or recycling on one does not affect the other. This is useful for
+    /// speculative execution or for testing "what if" scenarios against a snapshot of live state.
+    ///
+    /// Contrast this with `Clone`, which returns another handle to the *same* allocator, so that
+    /// PIDs allocated through either handle are visible through both.
+    ///
+    /// Note that live `Pid` handles always recycle into the allocator they were allocated from, never into a fork.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    ///
+    /// let fork = allocator.fork();
+    /// assert!(fork.contains(*pid));
+    ///
+    /// let forked_pid = fork.allocate().expect("Failed to allocate PID");
+    /// assert!(!allocator.contains(*forked_pid), "The original allocator is unaffected");
+    ///
+    /// let shared = allocator.clone();
+    /// let shared_pid = shared.allocate().expect("Failed to allocate PID");
+    /// assert!(allocator.contains(*shared_pid), "Clones share the same state");
+    /// ```
+    pub fn fork(&self) -> Self {
+        Self::from_inner(self.inner.lock().fork())
+    }
+
+    /// Copies the allocator's state into a fresh allocator with `M` layers, if every allocated PID fits.
+    ///
+    /// This is meant for shrinking an allocator whose upper layers are never used: the first `M`
+    /// layers are deep-copied, like [`PidAllocator::fork`], into a new allocator with capacity
+    /// `M * W::BITS`. Returns `None` if any PID at or above that capacity is allocated. The policy,
+    /// the quarantine, the pin set by [`PidAllocator::pin_low`], sequence tracking and the bound set
+    /// by [`PidAllocator::with_max`] carry over, clamped to the new capacity. `M` must not exceed
+    /// `ORDER`, or the call fails to compile.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<64>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    ///
+    /// let narrow = allocator.try_narrow::<32>().expect("No PID above 32 layers is allocated");
+    /// assert!(narrow.contains(*pid));
+    /// assert_eq!(PidAllocator::<32>::CAPACITY, 32 * usize::BITS as usize);
+    ///
+    /// let _high = allocator.allocate_at(PidAllocator::<32>::CAPACITY);
+    /// assert!(allocator.try_narrow::<32>().is_none());
+    /// ```
+    ///
+    /// Narrowing to more layers than the allocator has is rejected at compile time:
+    ///
+    /// ```compile_fail
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let widened = PidAllocator::<8>::new().try_narrow::<16>();
+    /// ```
+    pub fn try_narrow<const M: usize>(&self) -> Option<PidAllocator<M, W, R>> {
+        self.inner.lock().narrow().map(PidAllocator::from_inner)
+    }
+
+    /// Recomputes the summary of full layers from the bitmap itself.
+    ///
+    /// The allocator keeps a one-bit-per-layer summary that marks which layers are full. Every
+    /// method of this crate keeps it in sync, and the bulk constructors such as [`PidAllocator::from_bytes`]
+    /// and [`PidAllocator::merge`] rebuild it with this method. It is exposed so that code manipulating
+    /// the state directly can repair the invariant afterwards; calling it on a consistent allocator is a no-op.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _pid = allocator.allocate().expect("Failed to allocate PID");
+    /// allocator.recompute_top();
+    /// assert_eq!(allocator.allocated_count(), 1);
+    /// ```
+    pub fn recompute_top(&self) {
+        self.inner.lock().recompute_top();
+    }
+
+    /// Verifies the internal bookkeeping of the allocator, returning a description of the first violation found.
+    ///
+    /// This is cheap enough to call after every operation in tests. It checks that the summary of full
+    /// layers matches the bitmap (see [`PidAllocator::recompute_top`]), that every layer the allocator
+    /// skips as full really is full, and that quarantined PIDs are still marked in the bitmap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(allocator.check_invariants(), Ok(()));
+    /// ```
+    pub fn check_invariants(&self) -> Result<(), &'static str> {
+        self.inner.lock().check_invariants()
+    }
+
+    /// Imports every PID allocated in `other` into this allocator, e.g. when consolidating two pools.
+    ///
+    /// The merge is all or nothing: if a PID allocated in `other` is already allocated here (or lies
+    /// at or above this allocator's `max`), nothing is imported and that PID is returned as the error.
+    /// `other` is snapshotted before this allocator is locked, so merging two allocators into each other
+    /// from different threads cannot deadlock, and `other` itself is left unchanged.
+    ///
+    /// # Parameters
+    ///
+    /// * `other`: The allocator whose allocations should be imported.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if every PID was imported.
+    /// * `Err(number)` holding the lowest conflicting PID otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let other = PidAllocator::<8>::new();
+    /// let _pid = other.allocate_at(42).expect("Failed to allocate PID");
+    ///
+    /// assert_eq!(allocator.merge(&other), Ok(()));
+    /// assert!(allocator.contains(42));
+    /// assert_eq!(allocator.merge(&other), Err(42));
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// Imported PIDs are not owned by any `Pid` handle here. Free them explicitly with [`PidAllocator::try_recycle`].
+    pub fn merge(&self, other: &Self) -> Result<(), usize> {
+        let other = other.inner.lock().fork();
+        self.inner.lock().merge(&other)
+    }
+
+    /// Exports the allocation bitmap as a byte vector.
+    ///
+    /// Each layer is written as a little-endian `W`, lowest layer first, so the result
+    /// is [`PidAllocator::BITMAP_BYTES`] long. Bit `n` of the bitmap corresponds to PID `n`.
+    /// This is intended for snapshotting allocator state (e.g. to flash) without pulling in serde.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _pid = allocator.allocate().expect("Failed to allocate PID");
+    ///
+    /// let bytes = allocator.as_bytes();
+    /// assert_eq!(bytes.len(), PidAllocator::<8>::BITMAP_BYTES);
+    /// assert_eq!(bytes[0], 0b1);
+    /// ```
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.inner.lock().as_bytes()
+    }
+
+    /// Renders the allocation bitmap for debugging.
+    ///
+    /// The returned value implements [`Display`](fmt::Display) and prints one line per layer,
+    /// with one `1` (allocated) or `0` (free) character per PID, lowest PID first. The bitmap is
+    /// captured when this method is called, so later allocations do not change the output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<2, u8>::new();
+    /// let _pid = allocator.allocate_at(1).expect("Failed to allocate PID");
+    /// assert_eq!(allocator.debug_bitmap().to_string(), "01000000\n00000000");
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// PIDs waiting in a quarantine (see [`PidAllocator::with_quarantine`]) still have their bit set and render as `1`.
+    pub fn debug_bitmap(&self) -> impl fmt::Display {
+        DebugBitmap {
+            layers: self.inner.lock().bottom_layers,
+        }
+    }
+
+    /// Captures which PIDs are currently allocated, to be compared against the allocator later with
+    /// [`PidSnapshot::diff`].
+    ///
+    /// The bitmap is copied under a single lock. Quarantined and pinned PIDs are not allocated and
+    /// are left out, matching [`PidAllocator::contains`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let first = allocator.allocate().expect("Failed to allocate PID");
+    /// let snapshot = allocator.snapshot();
+    ///
+    /// let second = allocator.allocate().expect("Failed to allocate PID");
+    /// drop(first);
+    /// assert_eq!(snapshot.diff(&allocator), (vec![*second], vec![0]));
+    /// ```
+    pub fn snapshot(&self) -> PidSnapshot<ORDER, W> {
+        PidSnapshot {
+            layers: self.inner.lock().allocated_layers(),
+        }
+    }
+
+    /// Returns a snapshot of the currently allocated PIDs as a `HashSet`, taken under a single lock.
+    ///
+    /// This bridges to application code that works with std collections, and is easier to compare
+    /// or diff than the raw bitmap. Quarantined PIDs are not included, matching [`PidAllocator::contains`].
+    ///
+    /// This method is only available with the `std` feature enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// use std::collections::HashSet;
+    ///
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let _a = allocator.allocate_at(3).expect("Failed to allocate PID");
+    /// let _b = allocator.allocate_at(70).expect("Failed to allocate PID");
+    /// assert_eq!(allocator.to_set(), HashSet::from([3, 70]));
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn to_set(&self) -> std::collections::HashSet<usize> {
+        let inner = self.inner.lock();
+        inner
+            .set_bits()
+            .filter(|&number| inner.contains(number))
+            .collect()
+    }
+
+    /// Reconstructs an allocator from a bitmap previously produced by [`PidAllocator::as_bytes`].
+    ///
+    /// Every PID whose bit is set in `bytes` is marked as allocated in the new allocator. Note that
+    /// no `Pid` handles exist for these numbers, so they stay allocated until recycled through the allocator.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(PidAllocator<ORDER, W>)` if `bytes` is exactly `ORDER * W::BYTES` bytes long.
+    /// * `None` if the length does not match.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    ///
+    /// let restored = PidAllocator::<8>::from_bytes(&allocator.as_bytes()).expect("Invalid length");
+    /// assert!(restored.contains(*pid));
+    /// assert!(PidAllocator::<8>::from_bytes(&[0; 3]).is_none());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        PidAllocatorInner::from_bytes(bytes).map(Self::from_inner)
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> PidAllocatorInner<ORDER, W> {
+    /// The number of PIDs tracked by a single layer word.
+    const BITS_PER_LAYER: usize = W::BITS as usize;
+
+    /// The number of PIDs tracked by all layers. Overflowing `usize` is a compile-time error.
+    pub(crate) const CAPACITY: usize = match ORDER.checked_mul(Self::BITS_PER_LAYER) {
+        Some(capacity) => capacity,
+        None => panic!("PidAllocator capacity ORDER * W::BITS overflows usize"),
+    };
+
+    pub(crate) const fn new() -> Self {
+        Self::with_max(Self::CAPACITY)
+    }
+
+    /// Creates an empty state that never hands out PIDs at or above `max`.
+    pub(crate) const fn with_max(max: usize) -> Self {
+        Self {
+            top_layer: 0,
+            bottom_layers: [W::ZERO; ORDER],
+            hint_layer: 0,
+            max: if max < Self::CAPACITY {
+                max
+            } else {
+                Self::CAPACITY
+            },
+            quarantine: VecDeque::new(),
+            quarantine_depth: 0,
+            pin_floor: 0,
+            pinned: Vec::new(),
+            allocations: 0,
+            failures: 0,
+            policy: AllocationPolicy::LowestFirst,
+            cursor: 0,
+            rng_state: Self::seed(0),
+            recycle_hook: None,
+            sequences: Vec::new(),
+            next_sequence: 0,
+            #[cfg(feature = "async")]
+            waiters: VecDeque::new(),
+            #[cfg(feature = "async")]
+            next_waiter: 0,
+        }
+    }
+
+    /// Splits a PID number into its layer index and the bit index within that layer.
+    fn locate(number: usize) -> (usize, usize) {
+        (number / Self::BITS_PER_LAYER, number % Self::BITS_PER_LAYER)
+    }
+
+    /// Allocates a PID from the internal state. This method should only be called with exclusive access to the state.
+    #[inline]
+    pub(crate) fn allocate(&mut self) -> Option<usize> {
+        let number = self.allocate_attempt();
+        if number.is_none() {
+            self.failures = self.failures.wrapping_add(1);
+        }
+        number
+    }
+
+    /// Like `allocate`, but does not count a failure.
+    #[inline]
+    fn allocate_attempt(&mut self) -> Option<usize> {
+        self.release_quarantine();
+        let number = self.allocate_free()?;
+        self.tick(number);
+        Some(number)
+    }
+
+    /// Like `allocate`, but only commits the chosen PID if `pred` accepts it.
+    pub(crate) fn allocate_if(&mut self, pred: impl FnOnce(usize) -> bool) -> Option<usize> {
+        self.release_quarantine();
+        let Some(number) = self.next_free() else {
+            self.failures = self.failures.wrapping_add(1);
+            return None;
+        };
+        if !pred(number) {
+            return None;
+        }
+        self.allocate_attempt()
+    }
+
+    /// Like `allocate`, but never hands out PID 0. If the policy would pick 0, the lowest free PID
+    /// above it is taken instead.
+    pub(crate) fn allocate_nonzero(&mut self) -> Option<usize> {
+        self.release_quarantine();
+        if self.next_free() != Some(0) {
+            return self.allocate();
+        }
+
+        let Some(number) = self.free_bits().nth(1) else {
+            self.failures = self.failures.wrapping_add(1);
+            return None;
+        };
+        // PID 0 stays free, so `hint_layer` must keep pointing at its layer.
+        match self.policy {
+            AllocationPolicy::LowestFirst | AllocationPolicy::HighestFirst => {}
+            AllocationPolicy::RoundRobin => self.cursor = number + 1,
+            AllocationPolicy::Random => self.rng_state = Self::xorshift(self.rng_state),
+        }
+        self.mark(number);
+        self.tick(number);
+        Some(number)
+    }
+
+    /// Like `allocate`, but if every free PID is quarantined, releases the oldest quarantined PID early and hands it out.
+    pub(crate) fn allocate_or_reuse_freed(&mut self) -> Option<usize> {
+        if let Some(number) = self.allocate_attempt() {
+            return Some(number);
+        }
+        if let Some((number, _)) = self.quarantine.pop_front() {
+            self.settle(number);
+        }
+        self.allocate()
+    }
+
+    /// Allocates the free PID chosen by the policy, leaving the quarantine and the allocation count untouched.
+    #[inline]
+    fn allocate_free(&mut self) -> Option<usize> {
+        let number = self.next_free()?;
+        match self.policy {
+            AllocationPolicy::LowestFirst => self.hint_layer = Self::locate(number).0,
+            AllocationPolicy::HighestFirst => {}
+            AllocationPolicy::RoundRobin => self.cursor = number + 1,
+            AllocationPolicy::Random => self.rng_state = Self::xorshift(self.rng_state),
+        }
+        self.mark(number);
+        Some(number)
+    }
+
+    /// Returns the free PID that `allocate_free` would pick under the current policy.
+    fn next_free(&self) -> Option<usize> {
+        match self.policy {
+            AllocationPolicy::LowestFirst => self.first_free_bit(),
+            AllocationPolicy::HighestFirst => self.last_free_bit(),
+            AllocationPolicy::RoundRobin => self.first_free_from(self.cursor),
+            AllocationPolicy::Random if self.max > 0 => {
+                self.first_free_from(self.rng_state as usize % self.max)
+            }
+            AllocationPolicy::Random => None,
+        }
+    }
+
+    /// Returns the lowest free PID at or above `start`, wrapping around to the lowest free PID overall.
+    fn first_free_from(&self, start: usize) -> Option<usize> {
+        self.free_bits()
+            .find(|&number| number >= start)
+            .or_else(|| self.first_free_bit())
+    }
+
+    /// Returns the highest free PID below `max`, scanning the layers from the top.
+    fn last_free_bit(&self) -> Option<usize> {
+        (0..ORDER).rev().find_map(|index| {
+            let base = index * Self::BITS_PER_LAYER;
+            let free = !self.bottom_layers[index] & W::low_mask(self.max.saturating_sub(base));
+            (free != W::ZERO)
+                .then(|| base + Self::BITS_PER_LAYER - 1 - free.leading_zeros() as usize)
+        })
+    }
+
+    /// Turns a user seed into a generator state, which must never be zero. Seed 0 gives the default state.
+    const fn seed(seed: u64) -> u64 {
+        match seed ^ 0x9E37_79B9_7F4A_7C15 {
+            0 => 0x9E37_79B9_7F4A_7C15,
+            state => state,
+        }
+    }
+
+    /// Advances a xorshift64 generator state.
+    fn xorshift(mut state: u64) -> u64 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+
+    /// Returns the lowest free PID below `max` in a single pass over the layers, starting at `hint_layer`.
+    ///
+    /// `allocate` and `peek_next` both build on this, and `allocate_at` shares the bit-setting half through `mark`.
+    pub(crate) fn first_free_bit(&self) -> Option<usize> {
+        self.free_bits().next()
+    }
+
+    /// Returns an iterator over every free PID below `max`, in ascending order.
+    ///
+    /// This is the one place that turns layer words into PID numbers: each non-full layer is inverted
+    /// and its set bits are yielded lowest first. Layers below `hint_layer` are skipped, since they are full,
+    /// and the search starts directly at the first non-full layer found in `top_layer`, so the first free
+    /// PID is located in constant time however many full layers precede it.
+    pub(crate) fn free_bits(&self) -> impl FusedIterator<Item = usize> + '_ {
+        let hint = self.hint_layer.min(ORDER);
+        let below_hint = 1usize
+            .checked_shl(hint as u32)
+            .map_or(usize::MAX, |bit| bit - 1);
+        let start = ((!(self.top_layer | below_hint)).trailing_zeros() as usize).min(ORDER);
+        self.bottom_layers[start..]
+            .iter()
+            .enumerate()
+            .filter(|(_, &layer)| layer != W::MAX)
+            .flat_map(move |(offset, &layer)| {
+                let base = (start + offset) * Self::BITS_PER_LAYER;
+                let mut free = !layer;
+                core::iter::from_fn(move || {
+                    if free == W::ZERO {
+                        return None;
+                    }
+                    let bit = free.trailing_zeros() as usize;
+                    free &= !W::bit(bit);
+                    Some(base + bit)
+                })
+            })
+            .take_while(move |&number| number < self.max)
+            .fuse()
+    }
+
+    /// Returns an iterator over every PID whose bit is set, allocated or quarantined, in ascending order.
+    pub(crate) fn set_bits(&self) -> impl FusedIterator<Item = usize> + '_ {
+        self.bottom_layers
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &layer)| {
+                let base = index * Self::BITS_PER_LAYER;
+                let mut set = layer;
+                core::iter::from_fn(move || {
+                    if set == W::ZERO {
+                        return None;
+                    }
+                    let bit = set.trailing_zeros() as usize;
+                    set &= !W::bit(bit);
+                    Some(base + bit)
+                })
+            })
+            .fuse()
+    }
+
+    /// Allocates `n` PIDs, or none at all. On failure, the claimed PIDs are rolled back and the
+    /// state is restored exactly: the allocation counters, the policy's search state, and any PID that
+    /// left the quarantine during the batch, whether it was freed or pinned. The failed batch itself
+    /// is counted as one failed allocation.
+    pub(crate) fn allocate_n(&mut self, n: usize) -> Option<Vec<usize>> {
+        // Even if every quarantined PID were released during the batch, no more could be claimed.
+        if n > self.free_in(0..self.max) + self.quarantine.len() {
+            self.failures = self.failures.wrapping_add(1);
+            return None;
+        }
+
+        let (hint_layer, cursor, rng_state) = (self.hint_layer, self.cursor, self.rng_state);
+        let (allocations, next_sequence) = (self.allocations, self.next_sequence);
+        let pinned = self.pinned.len();
+        let quarantine = self.quarantine.clone();
+        let quarantined_sequences: Vec<u64> = quarantine
+            .iter()
+            .filter_map(|&(number, _)| self.sequences.get(number).copied())
+            .collect();
+
+        let mut claimed = Vec::with_capacity(n);
+        for _ in 0..n {
+            match self.allocate_attempt() {
+                Some(number) => claimed.push(number),
+                None => {
+                    for number in claimed {
+                        self.release(number);
+                    }
+                    // `allocate` only ever pops quarantined PIDs from the front, and `settle` only
+                    // ever appends to `pinned`.
+                    let released = quarantine.len() - self.quarantine.len();
+                    for &(number, _) in quarantine.iter().take(released) {
+                        self.mark(number);
+                    }
+                    self.pinned.truncate(pinned);
+                    for (&(number, _), &sequence) in quarantine.iter().zip(&quarantined_sequences) {
+                        self.sequences[number] = sequence;
+                    }
+                    self.quarantine = quarantine;
+                    (self.hint_layer, self.cursor, self.rng_state) =
+                        (hint_layer, cursor, rng_state);
+                    (self.allocations, self.next_sequence) = (allocations, next_sequence);
+                    self.failures = self.failures.wrapping_add(1);
+                    return None;
+                }
+            }
+        }
+        Some(claimed)
+    }
+
+    /// Allocates the lowest run of `n` consecutive free PIDs below `max` and returns its first number.
+    pub(crate) fn allocate_contiguous(&mut self, n: usize) -> Option<usize> {
+        self.release_quarantine();
+        if n == 0 {
+            return Some(0);
+        }
+
+        let (mut start, mut len) = (0, 0);
+        for number in self.free_bits() {
+            if len > 0 && number == start + len {
+                len += 1;
+            } else {
+                (start, len) = (number, 1);
+            }
+            if len == n {
+                break;
+            }
+        }
+        if len < n {
+            return None;
+        }
+
+        for number in start..start + n {
+            self.mark(number);
+            self.tick(number);
+        }
+        Some(start)
+    }
+
+    /// Allocates the highest free PID below `max`, regardless of the policy.
+    pub(crate) fn allocate_highest(&mut self) -> Option<usize> {
+        self.release_quarantine();
+        let number = self.last_free_bit()?;
+        self.mark(number);
+        self.tick(number);
+        Some(number)
+    }
+
+    /// Allocates the lowest free PID within `range`, clamped to `max`.
+    pub(crate) fn allocate_in(&mut self, range: Range<usize>) -> Option<usize> {
+        self.release_quarantine();
+        let (start, end) = (range.start, range.end.min(self.max));
+        if start >= end {
+            return None;
+        }
+
+        let number = (start / Self::BITS_PER_LAYER..=(end - 1) / Self::BITS_PER_LAYER).find_map(
+            |index| {
+                let base = index * Self::BITS_PER_LAYER;
+                let mut free = !self.bottom_layers[index] & W::low_mask(end - base);
+                if start > base {
+                    free &= !W::low_mask(start - base);
+                }
+                (free != W::ZERO).then(|| base + free.trailing_zeros() as usize)
+            },
+        )?;
+
+        self.mark(number);
+        self.tick(number);
+        Some(number)
+    }
+
+    /// Allocates the lowest free PID below `max` that is a multiple of `align`, a power of two.
+    pub(crate) fn allocate_aligned(&mut self, align: usize) -> Option<usize> {
+        self.release_quarantine();
+        // Both `align` and the layer width are powers of two, so every layer has the same aligned
+        // positions, or none at all except the first bit of every `align / BITS_PER_LAYER`-th layer.
+        let aligned = (0..Self::BITS_PER_LAYER)
+            .step_by(align)
+            .fold(W::ZERO, |mask, bit| mask | W::bit(bit));
+        let number = (0..ORDER)
+            .step_by((align / Self::BITS_PER_LAYER).max(1))
+            .find_map(|index| {
+                let base = index * Self::BITS_PER_LAYER;
+                let free = !self.bottom_layers[index]
+                    & aligned
+                    & W::low_mask(self.max.saturating_sub(base));
+                (free != W::ZERO).then(|| base + free.trailing_zeros() as usize)
+            })?;
+
+        self.mark(number);
+        self.tick(number);
+        Some(number)
+    }
+
+    /// Returns the free PIDs of the given layer that lie below `max`, as set bits.
+    #[cfg(feature = "rand_core")]
+    fn free_mask(&self, index: usize) -> W {
+        let start = index * Self::BITS_PER_LAYER;
+        let valid = W::low_mask(self.max.saturating_sub(start));
+        !self.bottom_layers[index] & valid
+    }
+
+    /// Marks a randomly chosen free PID as allocated, scanning from a random starting layer.
+    #[cfg(feature = "rand_core")]
+    pub(crate) fn allocate_random(&mut self, rng: &mut impl RngCore) -> Option<usize> {
+        self.release_quarantine();
+        let start = if ORDER == 0 {
+            0
+        } else {
+            rng.next_u64() as usize % ORDER
+        };
+        let Some((index, free)) = (0..ORDER)
+            .map(|offset| (start + offset) % ORDER)
+            .map(|index| (index, self.free_mask(index)))
+            .find(|&(_, free)| free != W::ZERO)
+        else {
+            self.failures = self.failures.wrapping_add(1);
+            return None;
+        };
+
+        // Skip a random number of free bits within the chosen layer.
+        let mut free = free;
+        for _ in 0..rng.next_u64() as usize % free.count_ones() as usize {
+            free &= !W::bit(free.trailing_zeros() as usize);
+        }
+        let number = index * Self::BITS_PER_LAYER + free.trailing_zeros() as usize;
+
+        let allocated = self.allocate_at(number);
+        debug_assert!(allocated, "PID {} should have been free", number);
+        Some(number)
+    }
+
+    /// Returns the lowest free PID without marking it as allocated.
+    pub(crate) fn peek_next(&self) -> Option<usize> {
+        self.next_free()
+    }
+
+    /// Marks the given PID as allocated if it is free and within range.
+    /// Returns `true` on success. This method should only be called with exclusive access to the state.
+    pub(crate) fn allocate_at(&mut self, number: usize) -> bool {
+        self.release_quarantine();
+        if number >= self.max || self.is_set(number) {
+            return false;
+        }
+
+        self.mark(number);
+        self.tick(number);
+        true
+    }
+
+    /// Sets the bit of the given PID, keeping the top layer in sync.
+    #[inline]
+    fn mark(&mut self, number: usize) {
+        let (layer_index, bit_index) = Self::locate(number);
+        self.bottom_layers[layer_index] |= W::bit(bit_index);
+        if self.bottom_layers[layer_index] == W::MAX {
+            self.top_layer |= 1 << layer_index;
+        }
+    }
+
+    /// Marks a PID as allocated without handing out a handle, skipping it if it is already set or
+    /// lies at or above `max`. Returns whether it was marked.
+    pub(crate) fn import(&mut self, number: usize) -> bool {
+        if number >= self.max || self.is_set(number) {
+            return false;
+        }
+        self.mark(number);
+        true
+    }
+
+    /// Recycles the given PID, making it available for allocation again.
+    ///
+    /// In debug builds, recycling a PID that is already free panics, since it means the
+    /// number was freed twice. Release builds silently ignore the redundant free.
+    #[inline]
+    pub(crate) fn recycle(&mut self, number: usize) {
+        debug_assert!(
+            self.contains(number),
+            "double free: PID {} recycled while already free",
+            number
+        );
+
+        if self.quarantine_depth > 0 {
+            self.quarantine.push_back((number, self.allocations));
+        } else {
+            self.settle(number);
+        }
+        if let Some(hook) = &self.recycle_hook {
+            (hook.0)(number);
+        }
+    }
+
+    /// Moves every PID that has sat out `quarantine_depth` allocations back into the free pool.
+    fn release_quarantine(&mut self) {
+        while let Some(&(number, recycled_at)) = self.quarantine.front() {
+            if self.allocations.wrapping_sub(recycled_at) < self.quarantine_depth {
+                break;
+            }
+            self.quarantine.pop_front();
+            self.settle(number);
+        }
+    }
+
+    /// Frees a PID that is no longer in use, or keeps it set as pinned if it lies below `pin_floor`.
+    #[inline]
+    fn settle(&mut self, number: usize) {
+        if number < self.pin_floor {
+            self.pinned.push(number);
+        } else {
+            self.release(number);
+        }
+    }
+
+    /// Pins every PID below `n`, replacing any previous pin. Allocated PIDs stay allocated.
+    pub(crate) fn pin_low(&mut self, n: usize) {
+        self.unpin_low();
+        self.pin_floor = n.min(self.max);
+        self.pin_free();
+    }
+
+    /// Sets the bit of every free PID below `pin_floor` and records it as pinned.
+    fn pin_free(&mut self) {
+        for number in 0..self.pin_floor {
+            if !self.is_set(number) {
+                self.mark(number);
+                self.pinned.push(number);
+            }
+        }
+    }
+
+    /// Lifts the pin, freeing every pinned PID that is not allocated. Returns how many were freed.
+    pub(crate) fn unpin_low(&mut self) -> usize {
+        self.pin_floor = 0;
+        let pinned = core::mem::take(&mut self.pinned);
+        for &number in &pinned {
+            self.release(number);
+        }
+        pinned.len()
+    }
+
+    /// Frees every PID, including quarantined ones, and returns the numbers that were allocated, in ascending order.
+    pub(crate) fn drain(&mut self) -> Vec<usize> {
+        let drained = self
+            .set_bits()
+            .filter(|&number| self.contains(number))
+            .collect();
+        self.top_layer = 0;
+        self.bottom_layers = [W::ZERO; ORDER];
+        self.hint_layer = 0;
+        self.quarantine.clear();
+        self.pinned.clear();
+        self.pin_free();
+        self.sequences.fill(0);
+        drained
+    }
+
+    /// Recycles every allocated PID rejected by `keep`. Returns how many were recycled.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(usize) -> bool) -> usize {
+        let rejected: Vec<usize> = self
+            .set_bits()
+            .filter(|&number| self.contains(number))
+            .filter(|&number| !keep(number))
+            .collect();
+        for &number in &rejected {
+            self.recycle(number);
+        }
+        rejected.len()
+    }
+
+    /// Clears every bit in `range`, clamped to the capacity, and drops quarantined PIDs inside it.
+    /// Returns how many allocated PIDs were freed.
+    pub(crate) fn clear_range(&mut self, range: Range<usize>) -> usize {
+        let (start, end) = (range.start, range.end.min(Self::CAPACITY));
+        if start >= end {
+            return 0;
+        }
+
+        let before = self.allocated_count();
+        for index in start / Self::BITS_PER_LAYER..=(end - 1) / Self::BITS_PER_LAYER {
+            let base = index * Self::BITS_PER_LAYER;
+            let mut cleared = W::low_mask(end - base);
+            if start > base {
+                cleared &= !W::low_mask(start - base);
+            }
+            self.bottom_layers[index] &= !cleared;
+            self.top_layer &= !(1 << index);
+        }
+        self.quarantine
+            .retain(|&(number, _)| !(start..end).contains(&number));
+        self.pinned.retain(|number| !(start..end).contains(number));
+        self.hint_layer = self.hint_layer.min(start / Self::BITS_PER_LAYER);
+        self.pin_free();
+        if let Some(sequences) = self.sequences.get_mut(start..end) {
+            sequences.fill(0);
+        }
+        before - self.allocated_count()
+    }
+
+    /// Returns the bottom layers with the bits of quarantined and pinned PIDs cleared.
+    pub(crate) fn allocated_layers(&self) -> [W; ORDER] {
+        let mut layers = self.bottom_layers;
+        let unallocated = self.quarantine.iter().map(|&(number, _)| number);
+        for number in unallocated.chain(self.pinned.iter().copied()) {
+            let (layer_index, bit_index) = Self::locate(number);
+            layers[layer_index] &= !W::bit(bit_index);
+        }
+        layers
+    }
+
+    /// Returns the lowest allocated PID, skipping quarantined and pinned ones.
+    pub(crate) fn min_allocated(&self) -> Option<usize> {
+        self.set_bits().find(|&number| self.contains(number))
+    }
+
+    /// Returns the highest allocated PID, scanning the layers from the top and skipping quarantined
+    /// and pinned ones.
+    pub(crate) fn max_allocated(&self) -> Option<usize> {
+        (0..ORDER).rev().find_map(|index| {
+            let base = index * Self::BITS_PER_LAYER;
+            let mut set = self.bottom_layers[index];
+            while set != W::ZERO {
+                let bit = Self::BITS_PER_LAYER - 1 - set.leading_zeros() as usize;
+                if self.contains(base + bit) {
+                    return Some(base + bit);
+                }
+                set &= !W::bit(bit);
+            }
+            None
+        })
+    }
+
+    /// Returns the length of the longest run of consecutive free PIDs and the total number of free PIDs.
+    pub(crate) fn free_run_stats(&self) -> (usize, usize) {
+        let (mut longest, mut free, mut run, mut previous) = (0, 0, 0, None);
+        for number in self.free_bits() {
+            run = match previous {
+                Some(previous) if previous + 1 == number => run + 1,
+                _ => 1,
+            };
+            longest = longest.max(run);
+            free += 1;
+            previous = Some(number);
+        }
+        (longest, free)
+    }
+
+    /// Clears the bit of the given PID, making it available for allocation again.
+    ///
+    /// A layer with a free bit is never full, so the top bit is cleared unconditionally, which keeps
+    /// this hot path free of branches on the bitmap.
+    #[inline]
+    fn release(&mut self, number: usize) {
+        let (layer_index, bit_index) = Self::locate(number);
+        self.bottom_layers[layer_index] &= !W::bit(bit_index);
+        self.top_layer &= !(1 << layer_index);
+        self.hint_layer = self.hint_layer.min(layer_index);
+        if let Some(sequence) = self.sequences.get_mut(number) {
+            *sequence = 0;
+        }
+    }
+
+    /// Counts a successful allocation of `number`, recording its sequence number if tracking is enabled.
+    #[inline]
+    fn tick(&mut self, number: usize) {
+        self.allocations = self.allocations.wrapping_add(1);
+        if let Some(sequence) = self.sequences.get_mut(number) {
+            self.next_sequence += 1;
+            *sequence = self.next_sequence;
+        }
+    }
+
+    /// Returns the sequence number at which an allocated PID was handed out, if it is tracked.
+    pub(crate) fn allocation_seq(&self, number: usize) -> Option<u64> {
+        let sequence = *self.sequences.get(number)?;
+        (sequence != 0 && self.contains(number)).then_some(sequence)
+    }
+
+    /// Recycles the given PID if it is currently allocated.
+    /// Returns `true` if the bit was set before clearing, and `false` for free or out-of-range numbers.
+    pub(crate) fn try_recycle(&mut self, number: usize) -> bool {
+        if !self.contains(number) {
+            return false;
+        }
+        self.recycle(number);
+        true
+    }
+
+    /// Returns the number of allocated PIDs by summing the population count of every layer.
+    pub fn allocated_count(&self) -> usize {
+        let set: usize = self
+            .bottom_layers
+            .iter()
+            .map(|layer| layer.count_ones() as usize)
+            .sum();
+        set - self.quarantine.len() - self.pinned.len()
+    }
+
+    /// Returns `true` if every PID is allocated, quarantined or pinned.
+    pub(crate) fn is_full(&self) -> bool {
+        self.peek_next().is_none()
+    }
+
+    /// Classifies `range` by how many of its PIDs are allocated. Quarantined and pinned PIDs count as free.
+    pub(crate) fn range_status(&self, range: Range<usize>) -> RangeStatus {
+        let len = range.end.saturating_sub(range.start);
+        match self.allocated_in(range) {
+            0 => RangeStatus::Free,
+            allocated if allocated == len => RangeStatus::Allocated,
+            _ => RangeStatus::Mixed,
+        }
+    }
+
+    /// Counts the free PIDs in `range` that lie below `max`. Quarantined and pinned PIDs are not free.
+    pub(crate) fn free_in(&self, range: Range<usize>) -> usize {
+        let end = range.end.min(self.max);
+        end.saturating_sub(range.start) - self.set_in(range.start..end)
+    }
+
+    /// Counts the allocated PIDs in `range`, clamped to the capacity. Quarantined and pinned PIDs are not.
+    fn allocated_in(&self, range: Range<usize>) -> usize {
+        let unallocated = self
+            .quarantine
+            .iter()
+            .map(|&(number, _)| number)
+            .chain(self.pinned.iter().copied())
+            .filter(|number| range.contains(number))
+            .count();
+        self.set_in(range) - unallocated
+    }
+
+    /// Counts the PIDs in `range` whose bit is set, clamped to the capacity, with a population count
+    /// over the masked layer words.
+    fn set_in(&self, range: Range<usize>) -> usize {
+        let (start, end) = (range.start, range.end.min(Self::CAPACITY));
+        if start >= end {
+            return 0;
+        }
+        (start / Self::BITS_PER_LAYER..=(end - 1) / Self::BITS_PER_LAYER)
+            .map(|index| {
+                let base = index * Self::BITS_PER_LAYER;
+                let mut set = self.bottom_layers[index] & W::low_mask(end - base);
+                if start > base {
+                    set &= !W::low_mask(start - base);
+                }
+                set.count_ones() as usize
+            })
+            .sum()
+    }
+
+    /// Checks whether a given PID is currently allocated. Quarantined PIDs are not.
+    pub(crate) fn contains(&self, number: usize) -> bool {
+        self.is_set(number)
+            && !self
+                .quarantine
+                .iter()
+                .any(|&(quarantined, _)| quarantined == number)
+            && !(number < self.pin_floor && self.pinned.contains(&number))
+    }
+
+    /// Checks whether the bit of a given PID is set, i.e. it is allocated or quarantined.
+    fn is_set(&self, number: usize) -> bool {
+        let (layer_index, bit_index) = Self::locate(number);
+
+        if layer_index < self.bottom_layers.len() {
+            (self.bottom_layers[layer_index] & W::bit(bit_index)) != W::ZERO
+        } else {
+            false
+        }
+    }
+
+    /// Writes the occupancy summary used by the `Debug` impls under the given struct name.
+    fn summarize(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        f.debug_struct(name)
+            .field("allocated", &self.allocated_count())
+            .field("free", &self.free_in(0..self.max))
+            .field("capacity", &self.max)
+            .finish()
+    }
+
+    /// Copies the state into a fresh one with `M <= ORDER` layers, failing if any allocated PID lies
+    /// in a layer beyond `M`. Like `fork`, the quarantine, the pin and sequence tracking carry over,
+    /// limited to the PIDs that still exist.
+    pub(crate) fn narrow<const M: usize>(&self) -> Option<PidAllocatorInner<M, W>> {
+        const { assert!(M <= ORDER, "narrowing must not add layers") };
+        let layers = self.allocated_layers();
+        if layers.iter().skip(M).any(|&layer| layer != W::ZERO) {
+            return None;
+        }
+
+        let mut narrow = PidAllocatorInner::with_max(self.max);
+        for (mine, theirs) in narrow.bottom_layers.iter_mut().zip(layers) {
+            *mine = theirs;
+        }
+        narrow.quarantine_depth = self.quarantine_depth;
+        for &(number, recycled_at) in &self.quarantine {
+            if number < narrow.max {
+                narrow.mark(number);
+                narrow.quarantine.push_back((number, recycled_at));
+            }
+        }
+        narrow.pin_floor = self.pin_floor.min(narrow.max);
+        narrow.pin_free();
+        narrow.recompute_top();
+        if !self.sequences.is_empty() {
+            narrow.sequences = self.sequences[..PidAllocatorInner::<M, W>::CAPACITY].to_vec();
+        }
+        narrow.next_sequence = self.next_sequence;
+        narrow.allocations = self.allocations;
+        narrow.failures = self.failures;
+        narrow.policy = self.policy;
+        narrow.cursor = self.cursor;
+        narrow.rng_state = self.rng_state;
+        Some(narrow)
+    }
+
+    /// Copies the allocation bitmap into a fresh state that shares nothing with `self`.
+    pub(crate) fn fork(&self) -> Self {
+        let mut fork = Self::new();
+        fork.top_layer = self.top_layer;
+        fork.bottom_layers = self.bottom_layers;
+        fork.hint_layer = self.hint_layer;
+        fork.max = self.max;
+        fork.quarantine = self.quarantine.clone();
+        fork.quarantine_depth = self.quarantine_depth;
+        fork.pin_floor = self.pin_floor;
+        fork.pinned = self.pinned.clone();
+        fork.allocations = self.allocations;
+        fork.failures = self.failures;
+        fork.sequences = self.sequences.clone();
+        fork.next_sequence = self.next_sequence;
+        fork.policy = self.policy;
+        fork.cursor = self.cursor;
+        fork.rng_state = self.rng_state;
+        fork
+    }
+
+    /// ORs the allocations of `other` into this state, failing without changes on the lowest PID
+    /// that is already set here or lies at or above `max`. Quarantined and pinned PIDs of `other` are not imported.
+    pub(crate) fn merge(&mut self, other: &Self) -> Result<(), usize> {
+        let mut imported = other.bottom_layers;
+        for &(number, _) in &other.quarantine {
+            let (layer_index, bit_index) = Self::locate(number);
+            imported[layer_index] &= !W::bit(bit_index);
+        }
+        for &number in &other.pinned {
+            let (layer_index, bit_index) = Self::locate(number);
+            imported[layer_index] &= !W::bit(bit_index);
+        }
+
+        for (index, (&mine, &theirs)) in self.bottom_layers.iter().zip(&imported).enumerate() {
+            let base = index * Self::BITS_PER_LAYER;
+            let out_of_range = !W::low_mask(self.max.saturating_sub(base));
+            let conflicts = (mine | out_of_range) & theirs;
+            if conflicts != W::ZERO {
+                return Err(base + conflicts.trailing_zeros() as usize);
+            }
+        }
+
+        for (layer, theirs) in self.bottom_layers.iter_mut().zip(imported) {
+            *layer |= theirs;
+        }
+        self.recompute_top();
+        Ok(())
+    }
+
+    /// Returns the first violated invariant of the state, if any.
+    pub(crate) fn check_invariants(&self) -> Result<(), &'static str> {
+        if ORDER < usize::BITS as usize && self.top_layer >> ORDER != 0 {
+            return Err("top_layer has bits set beyond ORDER");
+        }
+        for (index, &layer) in self.bottom_layers.iter().enumerate() {
+            let full = layer == W::MAX;
+            if full != (self.top_layer & 1 << index != 0) {
+                return Err("top_layer does not match the full bottom layers");
+            }
+            if index < self.hint_layer && !full {
+                return Err("a layer below hint_layer is not full");
+            }
+        }
+        if self
+            .quarantine
+            .iter()
+            .any(|&(number, _)| !self.is_set(number))
+        {
+            return Err("a quarantined PID is not marked in the bitmap");
+        }
+        if self
+            .pinned
+            .iter()
+            .any(|&number| number >= self.pin_floor || !self.is_set(number))
+        {
+            return Err("a pinned PID is not marked in the bitmap or lies above the pin");
+        }
+        Ok(())
+    }
+
+    /// Serializes the bottom layers as little-endian words, lowest layer first.
+    pub(crate) fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; ORDER * W::BYTES];
+        for (layer, chunk) in self
+            .bottom_layers
+            .iter()
+            .zip(bytes.chunks_exact_mut(W::BYTES))
+        {
+            layer.write_le_bytes(chunk);
+        }
+        bytes
+    }
+
+    /// Rebuilds the state from bytes produced by [`PidAllocatorInner::as_bytes`].
+    /// Returns `None` if the length does not match `ORDER` layers.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ORDER * W::BYTES {
+            return None;
+        }
+
+        let mut inner = Self::new();
+        for (layer, chunk) in inner
+            .bottom_layers
+            .iter_mut()
+            .zip(bytes.chunks_exact(W::BYTES))
+        {
+            *layer = W::read_le_bytes(chunk);
+        }
+        inner.recompute_top();
+        Some(inner)
+    }
+
+    /// Rebuilds `top_layer` from scratch, setting bit `i` exactly when bottom layer `i` is full.
+    /// Bulk operations that rewrite the bottom layers call this instead of updating bits one by one.
+    pub(crate) fn recompute_top(&mut self) {
+        self.top_layer = self
+            .bottom_layers
+            .iter()
+            .enumerate()
+            .filter(|(_, &layer)| layer == W::MAX)
+            .fold(0, |top, (index, _)| top | 1 << index);
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> PidAllocator<ORDER, W> {
+    /// Creates a new PID allocator that waits for its lock using the relax strategy `S`.
+    ///
+    /// The default strategy, [`Spin`], busy-waits with a spin-loop hint. On targets where that
+    /// wastes power, another strategy can yield to the scheduler or wait for an event instead.
+    /// Handles allocated from the returned allocator use the same strategy when they recycle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::{relax::Spin, PidAllocator};
+    ///
+    /// let allocator = PidAllocator::<8>::with_relax::<Spin>();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(*pid, 0);
+    /// ```
+    ///
+    /// With the `std` feature enabled, `relax::Yield` yields the thread while the lock is held:
+    ///
+    /// ```
+    /// # #[cfg(feature = "std")]
+    /// # {
+    /// use pid_allocator::{relax::Yield, PidAllocator};
+    ///
+    /// let allocator = PidAllocator::<8>::with_relax::<Yield>();
+    /// assert!(allocator.allocate().is_some());
+    /// # }
+    /// ```
+    pub fn with_relax<S: RelaxStrategy>() -> PidAllocator<ORDER, W, S> {
+        PidAllocator::new()
+    }
+}
+
+// `Default` and `Clone` are implemented by hand so that they do not require `R` to implement them.
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Default for PidAllocator<ORDER, W, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marks every number yielded by the iterator as allocated, e.g. to rebuild an allocator from a log.
+///
+/// As with [`PidAllocator::from_bytes`], the imported PIDs are not owned by any `Pid` handle; free them
+/// explicitly with [`PidAllocator::try_recycle`]. Numbers that are already allocated are ignored, and
+/// numbers the allocator cannot hold (at or above [`PidAllocator::CAPACITY`], or above the bound set by
+/// [`PidAllocator::with_max`]) are skipped rather than causing a panic.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::PidAllocator;
+///
+/// let mut allocator: PidAllocator<1, u8> = [1, 4].into_iter().collect();
+/// allocator.extend([4, 6, 100]);
+/// assert_eq!(allocator.allocated_count(), 3);
+/// assert!(allocator.contains(6));
+/// ```
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Extend<usize> for PidAllocator<ORDER, W, R> {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        // Lock per number, so that an iterator touching this allocator cannot deadlock.
+        for number in iter {
+            self.inner.lock().import(number);
+        }
+    }
+}
+
+/// Builds an allocator with every collected number marked as allocated. See the [`Extend`] impl for
+/// how duplicates and out-of-range numbers are handled.
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> FromIterator<usize>
+    for PidAllocator<ORDER, W, R>
+{
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut allocator = Self::new();
+        allocator.extend(iter);
+        allocator
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Clone for PidAllocator<ORDER, W, R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> Default for PidAllocatorInner<ORDER, W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The callback installed with [`PidAllocator::set_recycle_hook`].
+struct RecycleHook(Box<dyn Fn(usize) + Send + Sync>);
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> fmt::Debug for PidAllocator<ORDER, W, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Formatting must not spin on a lock the current thread may hold, e.g. through a `PidAllocatorGuard`.
+        match self.inner.try_lock() {
+            Some(inner) => inner.summarize(f, "PidAllocator"),
+            None => f
+                .debug_struct("PidAllocator")
+                .field("state", &format_args!("<locked>"))
+                .finish(),
+        }
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> fmt::Debug for PidAllocatorInner<ORDER, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.summarize(f, "PidAllocatorInner")
+    }
+}
+
+/// A snapshot of the bitmap layers returned by [`PidAllocator::debug_bitmap`].
+struct DebugBitmap<const ORDER: usize, W: BitWord> {
+    layers: [W; ORDER],
+}
+
+impl<const ORDER: usize, W: BitWord> fmt::Display for DebugBitmap<ORDER, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, &layer) in self.layers.iter().enumerate() {
+            if index > 0 {
+                f.write_str("\n")?;
+            }
+            for bit in 0..W::BITS as usize {
+                let set = layer & W::bit(bit) != W::ZERO;
+                f.write_str(if set { "1" } else { "0" })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A bare PID number with the same layout as `usize`, e.g. for map keys, domain newtypes and FFI.
+///
+/// A `RawPid` carries no lifecycle: it does not keep its PID allocated, is not recycled when dropped,
+/// and does not remember which allocator it came from. It is obtained from a live handle with
+/// [`Pid::raw`], and being `#[repr(transparent)]` over `usize`, can be passed to and from C as a plain
+/// integer.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use pid_allocator::{PidAllocator, RawPid};
+///
+/// let allocator = PidAllocator::<8>::new();
+/// let pid = allocator.allocate().expect("Failed to allocate PID");
+///
+/// let mut names = HashMap::new();
+/// names.insert(pid.raw(), "init");
+/// assert_eq!(names[&RawPid::from(0)], "init");
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RawPid(usize);
+
+impl RawPid {
+    /// Returns the PID number.
+    pub const fn get(self) -> usize {
+        self.0
+    }
+}
+
+impl From<usize> for RawPid {
+    fn from(number: usize) -> Self {
+        Self(number)
+    }
+}
+
+impl From<RawPid> for usize {
+    fn from(raw: RawPid) -> Self {
+        raw.0
+    }
+}
+
+impl fmt::Display for RawPid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// A handle to an allocated PID. When dropped, the PID is automatically recycled back into the allocator.
+///
+/// # Panics and unwinding
+///
+/// A `Pid` is recycled while unwinding too, so a thread that panics while holding one does not leak
+/// its PID. The allocator's spin lock has no poisoning: a panic while it is held, including one in
+/// caller code run under the lock, releases it while unwinding, so the allocator stays usable from
+/// other threads afterwards.
+///
+/// Some caller code does run under the lock: the hook passed to [`PidAllocator::set_recycle_hook`],
+/// the predicates of [`PidAllocator::allocate_if`] and [`PidAllocator::retain`], and everything done
+/// while a [`PidAllocatorGuard`] from [`PidAllocator::lock`] is alive. The lock is not re-entrant, so
+/// calling the allocator from there, or dropping one of its `Pid`s, deadlocks. The callback of
+/// [`PidAllocator::allocate_with`] is the exception: it runs only after the lock has been released.
+#[derive(Debug)]
+pub struct Pid<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    number: usize,
+    allocator: Arc<Shared<ORDER, W, R>>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Pid<ORDER, W, R> {
+    /// Returns a handle to the allocator this PID was allocated from.
+    ///
+    /// The returned [`PidAllocator`] shares its state with the original, just like a clone of it, so
+    /// code that only holds a `Pid` can allocate siblings from the same pool. With the `metrics`
+    /// feature, it also shares the original's [`PidAllocator::contention_count`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let pid = PidAllocator::<8>::new().allocate().expect("Failed to allocate PID");
+    /// let allocator = pid.allocator();
+    /// let sibling = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_ne!(*pid, *sibling);
+    /// assert!(allocator.contains(*pid));
+    /// ```
+    pub fn allocator(&self) -> PidAllocator<ORDER, W, R> {
+        PidAllocator {
+            inner: self.allocator.clone(),
+        }
+    }
+
+    /// Checks whether `other` was allocated from the same allocator as this PID.
+    ///
+    /// Numbers from different pools can coincide, so this lets defensive code check provenance
+    /// before, e.g., transferring or recycling a handle. Clones of an allocator share one pool and
+    /// therefore count as the same allocator, but forks do not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let (first, second) = (PidAllocator::<8>::new(), PidAllocator::<8>::new());
+    /// let a = first.allocate().expect("Failed to allocate PID");
+    /// let b = first.clone().allocate().expect("Failed to allocate PID");
+    /// let c = second.allocate().expect("Failed to allocate PID");
+    ///
+    /// assert!(a.same_allocator(&b));
+    /// assert!(!a.same_allocator(&c));
+    /// assert_eq!(*a, *c);
+    /// ```
+    pub fn same_allocator(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.allocator, &other.allocator)
+    }
+
+    /// Returns the PID's number as a [`RawPid`], leaving the handle and its lifecycle untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(pid.raw().get(), *pid);
+    /// ```
+    pub fn raw(&self) -> RawPid {
+        RawPid(self.number)
+    }
 }
 
-/// A handle to an allocated PID. When dropped, the PID is automatically recycled back into the allocator.
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for Pid<ORDER, W, R> {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Borrow<usize> for Pid<ORDER, W, R> {
+    fn borrow(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> AsRef<usize> for Pid<ORDER, W, R> {
+    fn as_ref(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PartialEq<usize> for Pid<ORDER, W, R> {
+    fn eq(&self, other: &usize) -> bool {
+        self.number == *other
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PartialEq<Pid<ORDER, W, R>> for usize {
+    fn eq(&self, other: &Pid<ORDER, W, R>) -> bool {
+        *self == other.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PartialOrd<usize> for Pid<ORDER, W, R> {
+    fn partial_cmp(&self, other: &usize) -> Option<cmp::Ordering> {
+        self.number.partial_cmp(other)
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PartialOrd<Pid<ORDER, W, R>> for usize {
+    fn partial_cmp(&self, other: &Pid<ORDER, W, R>) -> Option<cmp::Ordering> {
+        self.partial_cmp(&other.number)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> defmt::Format for Pid<ORDER, W, R> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=usize}", self.number)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> defmt::Format for PidAllocator<ORDER, W, R> {
+    /// Formats a compact summary of the allocator as `PidAllocator { allocated / capacity }`, or as
+    /// `PidAllocator { <locked> }` if the lock is held, like the `Debug` impl.
+    fn format(&self, f: defmt::Formatter) {
+        match self.inner.try_lock() {
+            Some(inner) => defmt::write!(
+                f,
+                "PidAllocator {{ {=usize} / {=usize} }}",
+                inner.allocated_count(),
+                inner.max
+            ),
+            None => defmt::write!(f, "PidAllocator {{ <locked> }}"),
+        }
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> From<Pid<ORDER, W, R>> for usize {
+    /// Consumes the handle and returns its number, leaving the PID allocated.
+    ///
+    /// The caller takes over the PID's lifecycle: it is no longer recycled automatically and
+    /// stays allocated until it is explicitly freed with [`PidAllocator::try_recycle`].
+    /// Unlike [`core::mem::forget`], the handle's reference to the allocator is still released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::PidAllocator;
+    ///
+    /// let allocator = PidAllocator::<8>::new();
+    /// let number = usize::from(allocator.allocate().expect("Failed to allocate PID"));
+    /// assert!(allocator.contains(number));
+    ///
+    /// assert!(allocator.try_recycle(number));
+    /// ```
+    ///
+    /// # Note
+    ///
+    /// Forgetting to recycle the returned number leaks the PID for the lifetime of the allocator.
+    /// Recycling it while another handle to the same number exists leads to that PID being handed out twice.
+    fn from(pid: Pid<ORDER, W, R>) -> Self {
+        let pid = ManuallyDrop::new(pid);
+        // SAFETY: `pid` is never dropped or used again, so the `Arc` is moved out exactly once.
+        drop(unsafe { core::ptr::read(&pid.allocator) });
+        pid.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop for Pid<ORDER, W, R> {
+    #[inline]
+    fn drop(&mut self) {
+        let mut inner = self.allocator.lock();
+        inner.recycle(self.number);
+        wake_waiter(inner);
+    }
+}
+
+/// Releases the lock after a PID has been recycled and, with the `async` feature,
+/// wakes one task waiting in `allocate_async` once the lock is no longer held.
+fn wake_waiter<const ORDER: usize, W: BitWord>(
+    inner: SpinMutexGuard<'_, PidAllocatorInner<ORDER, W>>,
+) {
+    #[cfg(feature = "async")]
+    {
+        let mut inner = inner;
+        let waiter = inner.waiters.pop_front();
+        drop(inner);
+        if let Some((_, waker)) = waiter {
+            waker.wake();
+        }
+    }
+    #[cfg(not(feature = "async"))]
+    drop(inner);
+}
+
+/// Like [`wake_waiter`], but wakes up to `freed` waiting tasks after a bulk recycle.
+pub(crate) fn wake_waiters<const ORDER: usize, W: BitWord>(
+    inner: SpinMutexGuard<'_, PidAllocatorInner<ORDER, W>>,
+    freed: usize,
+) {
+    #[cfg(feature = "async")]
+    {
+        let mut inner = inner;
+        let count = freed.min(inner.waiters.len());
+        let waiters: Vec<Waker> = inner
+            .waiters
+            .drain(..count)
+            .map(|(_, waker)| waker)
+            .collect();
+        drop(inner);
+        waiters.into_iter().for_each(Waker::wake);
+    }
+    #[cfg(not(feature = "async"))]
+    {
+        let _ = freed;
+        drop(inner);
+    }
+}
+
+/// The future driving [`PidAllocator::allocate_async`].
+#[cfg(feature = "async")]
+struct AllocateAsync<'a, const ORDER: usize, W: BitWord, R: RelaxStrategy> {
+    allocator: &'a PidAllocator<ORDER, W, R>,
+    /// The token this future registers in the allocator's waiters under, once it has been pending.
+    token: Option<u64>,
+}
+
+#[cfg(feature = "async")]
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Future for AllocateAsync<'_, ORDER, W, R> {
+    type Output = Pid<ORDER, W, R>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let allocator = self.allocator;
+        let mut inner = allocator.lock_for_allocation();
+        if let Some(number) = inner.allocate() {
+            // The future may complete on a poll it was not woken for, while still queued; a stale
+            // entry would swallow the wake-up of the next recycle.
+            if let Some(token) = self.token.take() {
+                inner.waiters.retain(|&(waiter, _)| waiter != token);
+            }
+            drop(inner);
+            return Poll::Ready(Pid {
+                number,
+                allocator: allocator.inner.clone(),
+            });
+        }
+
+        let token = *self.token.get_or_insert_with(|| {
+            inner.next_waiter = inner.next_waiter.wrapping_add(1);
+            inner.next_waiter
+        });
+        let waker = cx.waker();
+        match inner
+            .waiters
+            .iter_mut()
+            .find(|(waiter, _)| *waiter == token)
+        {
+            Some((_, queued)) => queued.clone_from(waker),
+            None => inner.waiters.push_back((token, waker.clone())),
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop for AllocateAsync<'_, ORDER, W, R> {
+    fn drop(&mut self) {
+        let Some(token) = self.token.take() else {
+            return;
+        };
+
+        let mut inner = self.allocator.inner.lock();
+        if let Some(position) = inner
+            .waiters
+            .iter()
+            .position(|&(waiter, _)| waiter == token)
+        {
+            inner.waiters.remove(position);
+            return;
+        }
+
+        // The cancelled future was already dequeued, so it consumed a wake-up meant to hand it a
+        // freed PID. Pass the wake-up on so the PID does not sit idle.
+        let next = if inner.is_full() {
+            None
+        } else {
+            inner.waiters.pop_front()
+        };
+        drop(inner);
+        if let Some((_, waker)) = next {
+            waker.wake();
+        }
+    }
+}
+
+/// A handle to an allocated PID that borrows its allocator, returned by [`PidAllocator::allocate_borrowed`]
+/// and [`StaticPidAllocator::allocate`](crate::StaticPidAllocator::allocate).
+/// When dropped, the PID is recycled through the borrow, without touching any reference count.
+#[derive(Debug)]
+pub struct BorrowedPid<'a, const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    pub(crate) number: usize,
+    pub(crate) allocator: &'a SpinMutex<PidAllocatorInner<ORDER, W>, R>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for BorrowedPid<'_, ORDER, W, R> {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Borrow<usize>
+    for BorrowedPid<'_, ORDER, W, R>
+{
+    fn borrow(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> AsRef<usize>
+    for BorrowedPid<'_, ORDER, W, R>
+{
+    fn as_ref(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop for BorrowedPid<'_, ORDER, W, R> {
+    fn drop(&mut self) {
+        let mut inner = self.allocator.lock();
+        inner.recycle(self.number);
+        wake_waiter(inner);
+    }
+}
+
+/// A batch of PIDs returned by [`PidAllocator::allocate_n`] and
+/// [`PidAllocator::allocate_batch_contiguous`], in allocation order.
+///
+/// The batch owns its [`Pid`] handles and dereferences to a slice of them. Iterating it by value
+/// yields the handles, while [`PidBatch::numbers`] views just the numbers without taking ownership.
+/// Dropping the batch recycles every PID still in it.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::PidAllocator;
+///
+/// let allocator = PidAllocator::<8>::new();
+/// let batch = allocator.allocate_n(3).expect("Failed to allocate PIDs");
+/// assert_eq!(batch.numbers().collect::<Vec<_>>(), [0, 1, 2]);
+///
+/// let handles: Vec<_> = batch.into_iter().collect();
+/// assert_eq!(handles.len(), 3);
+/// ```
+#[derive(Debug)]
+pub struct PidBatch<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    pids: Vec<Pid<ORDER, W, R>>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PidBatch<ORDER, W, R> {
+    /// Returns an iterator over the PID numbers in the batch, in allocation order.
+    pub fn numbers(&self) -> impl FusedIterator<Item = usize> + '_ {
+        self.pids.iter().map(|pid| pid.number)
+    }
+
+    /// Consumes the batch and returns its PID numbers without recycling them.
+    ///
+    /// Like converting each [`Pid`] into a `usize`, this hands the PIDs' lifecycle over to the caller:
+    /// they stay allocated until freed with [`PidAllocator::try_recycle`] or [`PidAllocator::recycle_all`].
+    pub fn into_numbers(self) -> impl FusedIterator<Item = usize> {
+        self.pids.into_iter().map(usize::from)
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for PidBatch<ORDER, W, R> {
+    type Target = [Pid<ORDER, W, R>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.pids
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> IntoIterator for PidBatch<ORDER, W, R> {
+    type Item = Pid<ORDER, W, R>;
+    type IntoIter = vec::IntoIter<Pid<ORDER, W, R>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pids.into_iter()
+    }
+}
+
+impl<'a, const ORDER: usize, W: BitWord, R: RelaxStrategy> IntoIterator
+    for &'a PidBatch<ORDER, W, R>
+{
+    type Item = &'a Pid<ORDER, W, R>;
+    type IntoIter = core::slice::Iter<'a, Pid<ORDER, W, R>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pids.iter()
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> From<PidBatch<ORDER, W, R>>
+    for Vec<Pid<ORDER, W, R>>
+{
+    fn from(batch: PidBatch<ORDER, W, R>) -> Self {
+        batch.pids
+    }
+}
+
+/// A copy of which PIDs were allocated at some point, returned by [`PidAllocator::snapshot`].
+///
+/// A snapshot does not keep its allocator alive or locked; it is a plain copy of the bitmap that
+/// can be compared against the live allocator, or any allocator of the same layout, with
+/// [`PidSnapshot::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PidSnapshot<const ORDER: usize, W: BitWord = usize> {
+    layers: [W; ORDER],
+}
+
+impl<const ORDER: usize, W: BitWord> PidSnapshot<ORDER, W> {
+    /// Compares the snapshot with the current state of `current`, returning the PIDs allocated since
+    /// the snapshot was taken and the PIDs freed since, both in ascending order.
+    ///
+    /// The bitmaps are compared a word at a time, and each differing bit is classified by which side
+    /// has it set. A PID that was freed and allocated again in between shows up in neither list.
+    pub fn diff<R: RelaxStrategy>(
+        &self,
+        current: &PidAllocator<ORDER, W, R>,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let layers = current.inner.lock().allocated_layers();
+        let (mut allocated, mut freed) = (Vec::new(), Vec::new());
+        for (index, (&then, &now)) in self.layers.iter().zip(&layers).enumerate() {
+            let base = index * W::BITS as usize;
+            for (mut changed, numbers) in [(now & !then, &mut allocated), (then & !now, &mut freed)]
+            {
+                while changed != W::ZERO {
+                    let bit = changed.trailing_zeros() as usize;
+                    changed &= !W::bit(bit);
+                    numbers.push(base + bit);
+                }
+            }
+        }
+        (allocated, freed)
+    }
+}
+
+/// A PID that is reserved but not yet handed out, returned by [`PidAllocator::reserve`].
+///
+/// The PID is allocated for as long as the reservation exists, so no one else can take it, but
+/// ownership is only settled at the end: [`Reservation::commit`] turns it into a regular [`Pid`],
+/// while [`Reservation::abort`] or simply dropping the reservation frees it again. Dereferences to
+/// the reserved PID number.
+#[derive(Debug)]
+pub struct Reservation<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    pid: Pid<ORDER, W, R>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Reservation<ORDER, W, R> {
+    /// Keeps the reserved PID, handing it over as a [`Pid`] that is recycled when dropped.
+    pub fn commit(self) -> Pid<ORDER, W, R> {
+        self.pid
+    }
+
+    /// Gives up the reservation and frees the PID. This is the same as dropping it.
+    pub fn abort(self) {}
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for Reservation<ORDER, W, R> {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pid
+    }
+}
+
+/// A block of reserved PIDs that are handed out one at a time, returned by [`PidAllocator::reserve_n`].
+///
+/// The PIDs stay allocated while the reservation exists, so each [`MultiReservation::draw`] is
+/// guaranteed to succeed until the reservation is used up. Dropping the reservation frees the PIDs
+/// that have not been drawn; drawn ones live on as regular [`Pid`]s.
+#[derive(Debug)]
+pub struct MultiReservation<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    allocator: PidAllocator<ORDER, W, R>,
+    numbers: vec::IntoIter<usize>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> MultiReservation<ORDER, W, R> {
+    /// Takes the next reserved PID, in allocation order, or returns `None` once all have been drawn.
+    pub fn draw(&mut self) -> Option<Pid<ORDER, W, R>> {
+        let number = self.numbers.next()?;
+        Some(Pid {
+            number,
+            allocator: self.allocator.inner.clone(),
+        })
+    }
+
+    /// Returns how many reserved PIDs have not been drawn yet.
+    pub fn remaining(&self) -> usize {
+        self.numbers.len()
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop for MultiReservation<ORDER, W, R> {
+    fn drop(&mut self) {
+        if self.numbers.len() > 0 {
+            self.allocator.recycle_all(self.numbers.by_ref());
+        }
+    }
+}
+
+/// A handle to an allocated PID that is guaranteed to be non-zero.
+///
+/// Returned by [`PidAllocator::allocate_nonzero`]. Dereferences to a [`NonZeroUsize`], and
+/// automatically recycles the PID back into the allocator when dropped.
+#[derive(Debug)]
+pub struct NonZeroPid<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    number: NonZeroUsize,
+    allocator: Arc<Shared<ORDER, W, R>>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for NonZeroPid<ORDER, W, R> {
+    type Target = NonZeroUsize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop for NonZeroPid<ORDER, W, R> {
+    fn drop(&mut self) {
+        let mut inner = self.allocator.lock();
+        inner.recycle(self.number.get());
+        wake_waiter(inner);
+    }
+}
+
+/// An iterator that allocates PIDs until the allocator is exhausted.
+///
+/// Returned by [`PidAllocator::allocations`] and, allocating from the top down, by
+/// [`PidAllocator::allocations_desc`].
+#[derive(Debug)]
+pub struct Allocations<'a, const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    allocator: &'a PidAllocator<ORDER, W, R>,
+    exhausted: bool,
+    descending: bool,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Iterator for Allocations<'_, ORDER, W, R> {
+    type Item = Pid<ORDER, W, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+        let pid = if self.descending {
+            self.allocator.allocate_highest()
+        } else {
+            self.allocator.allocate()
+        };
+        self.exhausted = pid.is_none();
+        pid
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> FusedIterator
+    for Allocations<'_, ORDER, W, R>
+{
+}
+
+/// Exclusive access to an allocator's state, returned by [`PidAllocator::lock`].
+///
+/// Every method runs under the lock the guard holds, so a sequence of calls on the guard is atomic
+/// with respect to all other users of the allocator. The lock is released when the guard is dropped.
+/// The underlying spin lock has no notion of poisoning: if a thread panics while holding the guard,
+/// unwinding drops it and the lock is simply released.
+///
+/// PIDs allocated through the guard are handed out as plain numbers rather than [`Pid`] handles,
+/// since dropping a handle while the guard is alive would need the lock the guard already holds.
+/// Like the numbers from [`PidBatch::into_numbers`], they stay allocated until recycled by number,
+/// e.g. with [`PidAllocatorGuard::try_recycle`] or [`PidAllocator::try_recycle`].
+///
+/// # Deadlocks
+///
+/// The lock is not re-entrant. While the guard is alive, the same thread must not call any method
+/// on the allocator itself, nor drop a [`Pid`] from it; either would spin forever waiting for the
+/// guard to be released.
 #[derive(Debug)]
-pub struct Pid<const ORDER: usize> {
+pub struct PidAllocatorGuard<'a, const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    /// The held lock, only taken out when the guard is dropped.
+    inner: Option<SpinMutexGuard<'a, PidAllocatorInner<ORDER, W>>>,
+    _allocator: PhantomData<&'a PidAllocator<ORDER, W, R>>,
+    freed: usize,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PidAllocatorGuard<'_, ORDER, W, R> {
+    /// Allocates a PID like [`PidAllocator::allocate`] and returns its number.
+    ///
+    /// The PID stays allocated until it is recycled by number.
+    pub fn allocate(&mut self) -> Option<usize> {
+        self.state_mut().allocate()
+    }
+
+    /// Allocates a specific PID like [`PidAllocator::allocate_at`], reporting whether it was free.
+    ///
+    /// The PID stays allocated until it is recycled by number.
+    pub fn allocate_at(&mut self, number: usize) -> bool {
+        self.state_mut().allocate_at(number)
+    }
+
+    /// Checks whether a given PID is currently allocated, like [`PidAllocator::contains`].
+    pub fn contains(&self, number: usize) -> bool {
+        self.state().contains(number)
+    }
+
+    /// Returns the PID the next allocation would hand out, like [`PidAllocator::peek_next`].
+    pub fn peek_next(&self) -> Option<usize> {
+        self.state().peek_next()
+    }
+
+    /// Recycles a PID by number, like [`PidAllocator::try_recycle`], reporting whether it was freed.
+    ///
+    /// Tasks waiting in `allocate_async` are woken once the guard is dropped.
+    pub fn try_recycle(&mut self, number: usize) -> bool {
+        let recycled = self.state_mut().try_recycle(number);
+        self.freed += usize::from(recycled);
+        recycled
+    }
+
+    /// Returns the number of currently allocated PIDs, like [`PidAllocator::allocated_count`].
+    pub fn allocated_count(&self) -> usize {
+        self.state().allocated_count()
+    }
+
+    /// Returns the number of successful allocations so far, like [`PidAllocator::total_allocations`].
+    pub fn total_allocations(&self) -> usize {
+        self.state().allocations
+    }
+
+    /// Returns the number of failed allocations so far, like [`PidAllocator::failed_allocations`].
+    pub fn failed_allocations(&self) -> usize {
+        self.state().failures
+    }
+
+    fn state(&self) -> &PidAllocatorInner<ORDER, W> {
+        self.inner
+            .as_ref()
+            .expect("the lock is only released on drop")
+    }
+
+    fn state_mut(&mut self) -> &mut PidAllocatorInner<ORDER, W> {
+        self.inner
+            .as_mut()
+            .expect("the lock is only released on drop")
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop for PidAllocatorGuard<'_, ORDER, W, R> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            wake_waiters(inner, self.freed);
+        }
+    }
+}
+
+/// A handle to an allocated PID that runs a user closure after the PID is recycled.
+///
+/// Returned by [`PidAllocator::allocate_with`]. It behaves like a [`Pid`], but once the PID has
+/// been returned to the allocator on drop, the closure is invoked with the freed PID number.
+pub struct PidGuard<
+    F: FnOnce(usize),
+    const ORDER: usize,
+    W: BitWord = usize,
+    R: RelaxStrategy = Spin,
+> {
     number: usize,
-    allocator: Arc<SpinMutex<PidAllocatorInner<ORDER>>>,
+    allocator: Arc<Shared<ORDER, W, R>>,
+    on_drop: Option<F>,
 }
 
-impl<const ORDER: usize> Deref for Pid<ORDER> {
+impl<F: FnOnce(usize), const ORDER: usize, W: BitWord, R: RelaxStrategy> fmt::Debug
+    for PidGuard<F, ORDER, W, R>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PidGuard")
+            .field("number", &self.number)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FnOnce(usize), const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref
+    for PidGuard<F, ORDER, W, R>
+{
     type Target = usize;
 
     fn deref(&self) -> &Self::Target {
@@ -233,8 +4158,15 @@ impl<const ORDER: usize> Deref for Pid<ORDER> {
     }
 }
 
-impl<const ORDER: usize> Drop for Pid<ORDER> {
+impl<F: FnOnce(usize), const ORDER: usize, W: BitWord, R: RelaxStrategy> Drop
+    for PidGuard<F, ORDER, W, R>
+{
     fn drop(&mut self) {
-        self.allocator.lock().recycle(self.number);
+        let mut inner = self.allocator.lock();
+        inner.recycle(self.number);
+        wake_waiter(inner);
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop(self.number);
+        }
     }
 }
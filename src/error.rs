@@ -0,0 +1,24 @@
+use core::fmt;
+
+/// Errors reported by the checked operations of the PID allocators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PidError {
+    /// The given number lies outside the allocator's capacity.
+    OutOfRange(usize),
+    /// The allocator's lock was held by another thread, and the operation was not allowed to wait for it.
+    WouldBlock,
+    /// No free PID was left to allocate.
+    Exhausted,
+}
+
+impl fmt::Display for PidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange(number) => write!(f, "PID {} is out of range", number),
+            Self::WouldBlock => write!(f, "the allocator is locked by another thread"),
+            Self::Exhausted => write!(f, "no free PID is left"),
+        }
+    }
+}
+
+impl core::error::Error for PidError {}
@@ -0,0 +1,93 @@
+//! C-compatible bindings for using the allocator from C or assembly, e.g. in a bootloader.
+//!
+//! Const generics cannot cross an FFI boundary, so these bindings expose a single allocator
+//! type with [`ORDER`] layers of `usize` words, behind an opaque [`FfiPidAllocator`] handle.
+//!
+//! # Ownership
+//!
+//! C code cannot run `Drop`, so PIDs handed out through these functions are plain numbers that
+//! never recycle themselves. Every PID returned by [`pid_allocator_allocate`] stays allocated until
+//! it is passed to [`pid_allocator_recycle`]. The handle returned by [`pid_allocator_new`] is owned
+//! by the caller and must be released with [`pid_allocator_free`] exactly once; PIDs still allocated
+//! at that point are simply discarded along with it.
+
+use alloc::boxed::Box;
+
+use crate::PidAllocator;
+
+/// The number of layers of the allocator exposed over FFI, giving `ORDER * usize::BITS` PIDs.
+pub const ORDER: usize = 32;
+
+/// The value returned by [`pid_allocator_allocate`] when no PID is available.
+pub const PID_ALLOCATOR_EXHAUSTED: usize = usize::MAX;
+
+/// An opaque handle to a PID allocator, only ever used behind a pointer from C.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiPidAllocator {
+    allocator: PidAllocator<ORDER>,
+}
+
+/// Creates a new, empty allocator and returns an owning pointer to it.
+///
+/// The pointer must eventually be released with [`pid_allocator_free`].
+#[no_mangle]
+pub extern "C" fn pid_allocator_new() -> *mut FfiPidAllocator {
+    Box::into_raw(Box::new(FfiPidAllocator {
+        allocator: PidAllocator::new(),
+    }))
+}
+
+/// Allocates the lowest free PID, returning [`PID_ALLOCATOR_EXHAUSTED`] if none is available
+/// or `allocator` is null.
+///
+/// The PID stays allocated until it is passed to [`pid_allocator_recycle`].
+///
+/// # Safety
+///
+/// `allocator` must be null or a pointer returned by [`pid_allocator_new`] that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pid_allocator_allocate(allocator: *const FfiPidAllocator) -> usize {
+    // SAFETY: The caller guarantees the pointer is null or valid.
+    let Some(handle) = (unsafe { allocator.as_ref() }) else {
+        return PID_ALLOCATOR_EXHAUSTED;
+    };
+    handle
+        .allocator
+        .allocate()
+        .map_or(PID_ALLOCATOR_EXHAUSTED, usize::from)
+}
+
+/// Recycles a PID previously returned by [`pid_allocator_allocate`].
+///
+/// Returns `true` if the PID was allocated and has been freed, and `false` if it was already free,
+/// out of range, or `allocator` is null.
+///
+/// # Safety
+///
+/// `allocator` must be null or a pointer returned by [`pid_allocator_new`] that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pid_allocator_recycle(
+    allocator: *const FfiPidAllocator,
+    pid: usize,
+) -> bool {
+    // SAFETY: The caller guarantees the pointer is null or valid.
+    match unsafe { allocator.as_ref() } {
+        Some(handle) => handle.allocator.try_recycle(pid),
+        None => false,
+    }
+}
+
+/// Releases an allocator created by [`pid_allocator_new`]. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `allocator` must be null or a pointer returned by [`pid_allocator_new`] that has not been freed yet.
+/// It must not be used again after this call.
+#[no_mangle]
+pub unsafe extern "C" fn pid_allocator_free(allocator: *mut FfiPidAllocator) {
+    if !allocator.is_null() {
+        // SAFETY: The caller guarantees the pointer came from `pid_allocator_new` and is freed only once.
+        drop(unsafe { Box::from_raw(allocator) });
+    }
+}
@@ -1,10 +1,32 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(any(test, feature = "std"))]
+extern crate std;
 
-pub use allocator::{Pid, PidAllocator};
+pub use allocator::{
+    required_order, AllocationPolicy, Allocations, BorrowedPid, NonZeroPid, Pid, PidAllocator,
+    PidAllocatorGuard, PidGuard, PidSnapshot, RangeStatus, RawPid,
+};
+pub use cache::{CachedPid, PidCache};
+pub use error::PidError;
+pub use local::{LocalPid, LocalPidAllocator};
+pub use namespace::{NamespacedPid, PidNamespace};
+pub use sharded::{ShardedPid, ShardedPidAllocator};
+pub use static_allocator::StaticPidAllocator;
+pub use word::BitWord;
 
 pub mod allocator;
+pub mod cache;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod local;
+pub mod namespace;
+pub mod relax;
+pub mod sharded;
+pub mod static_allocator;
+pub mod word;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,108 @@
+use core::{borrow, cell::RefCell, ops::Deref};
+
+use alloc::rc::Rc;
+
+use crate::{allocator::PidAllocatorInner, word::BitWord};
+
+/// A single-threaded PID allocator without atomic reference counting or locking.
+///
+/// `LocalPidAllocator` offers the same allocation API as [`PidAllocator`](crate::PidAllocator),
+/// but keeps its state in an `Rc<RefCell<...>>` instead of an `Arc<SpinMutex<...>>`. This makes it
+/// `!Send` and `!Sync`, so it can only be used from the thread that created it.
+///
+/// Prefer it over `PidAllocator` in single-threaded contexts (e.g. bare-metal firmware without
+/// preemption), where the atomic refcounting and spin lock of `PidAllocator` are pure overhead.
+/// If PIDs need to be allocated or recycled from more than one thread, use `PidAllocator` instead.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::LocalPidAllocator;
+///
+/// let allocator = LocalPidAllocator::<8>::new();
+///
+/// let pid = allocator.allocate().expect("Failed to allocate PID");
+/// assert!(allocator.contains(*pid));
+///
+/// let number = *pid;
+/// drop(pid);
+/// assert!(!allocator.contains(number));
+/// ```
+#[derive(Debug, Default)]
+pub struct LocalPidAllocator<const ORDER: usize, W: BitWord = usize> {
+    inner: Rc<RefCell<PidAllocatorInner<ORDER, W>>>,
+}
+
+impl<const ORDER: usize, W: BitWord> LocalPidAllocator<ORDER, W> {
+    /// Creates a new instance of the single-threaded PID allocator.
+    ///
+    /// The allocator supports up to `ORDER * W::BITS` unique PIDs.
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(PidAllocatorInner::new())),
+        }
+    }
+
+    /// Attempts to allocate a new PID. Returns `Some(LocalPid)` if successful, or `None` if all PIDs are currently allocated.
+    /// The allocated PID is wrapped in a `LocalPid` object, which will automatically recycle the PID when dropped.
+    pub fn allocate(&self) -> Option<LocalPid<ORDER, W>> {
+        let number = self.inner.borrow_mut().allocate()?;
+        Some(self.handle(number))
+    }
+
+    /// Attempts to allocate a specific PID. Returns `None` if it is already allocated or out of range.
+    pub fn allocate_at(&self, number: usize) -> Option<LocalPid<ORDER, W>> {
+        let allocated = self.inner.borrow_mut().allocate_at(number);
+        allocated.then(|| self.handle(number))
+    }
+
+    /// Attempts to allocate the `preferred` PID, falling back to any free PID if it is taken or out of range.
+    pub fn allocate_with_hint(&self, preferred: usize) -> Option<LocalPid<ORDER, W>> {
+        self.allocate_at(preferred).or_else(|| self.allocate())
+    }
+
+    /// Checks whether a given PID is currently allocated.
+    pub fn contains(&self, number: usize) -> bool {
+        self.inner.borrow().contains(number)
+    }
+
+    fn handle(&self, number: usize) -> LocalPid<ORDER, W> {
+        LocalPid {
+            number,
+            allocator: self.inner.clone(),
+        }
+    }
+}
+
+/// A handle to a PID allocated from a [`LocalPidAllocator`]. When dropped, the PID is automatically recycled.
+#[derive(Debug)]
+pub struct LocalPid<const ORDER: usize, W: BitWord = usize> {
+    number: usize,
+    allocator: Rc<RefCell<PidAllocatorInner<ORDER, W>>>,
+}
+
+impl<const ORDER: usize, W: BitWord> Deref for LocalPid<ORDER, W> {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> borrow::Borrow<usize> for LocalPid<ORDER, W> {
+    fn borrow(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> AsRef<usize> for LocalPid<ORDER, W> {
+    fn as_ref(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord> Drop for LocalPid<ORDER, W> {
+    fn drop(&mut self) {
+        self.allocator.borrow_mut().recycle(self.number);
+    }
+}
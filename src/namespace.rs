@@ -0,0 +1,115 @@
+use core::{borrow::Borrow, ops::Deref};
+
+use crate::{
+    allocator::Pid,
+    relax::{RelaxStrategy, Spin},
+    word::BitWord,
+    PidAllocator,
+};
+
+/// A child PID namespace, in the spirit of Linux PID namespaces.
+///
+/// A namespace owns a local [`PidAllocator`] whose numbers are independent of its parent's, and
+/// keeps a handle to the parent allocator. Every PID allocated in the namespace also consumes one
+/// PID in the parent, so a process is visible under two numbers: its local PID, as seen from inside
+/// the namespace, and its parent PID, as seen from the parent. The two numbers are unrelated; the
+/// mapping between them is carried only by the returned [`NamespacedPid`] handle.
+///
+/// Allocation fails if either the parent or the local allocator is exhausted, in which case nothing
+/// is left allocated in either. Dropping the handle recycles both PIDs.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::{PidAllocator, PidNamespace};
+///
+/// let parent = PidAllocator::<8>::new();
+/// let _init = parent.allocate().expect("Failed to allocate PID");
+///
+/// let namespace = PidNamespace::new(&parent);
+/// let pid = namespace.allocate().expect("Failed to allocate PID");
+/// assert_eq!(pid.local(), 0);
+/// assert_eq!(pid.parent(), 1);
+/// ```
+#[derive(Debug)]
+pub struct PidNamespace<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    parent: PidAllocator<ORDER, W, R>,
+    local: PidAllocator<ORDER, W, R>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> PidNamespace<ORDER, W, R> {
+    /// Creates an empty namespace nested inside `parent`.
+    pub fn new(parent: &PidAllocator<ORDER, W, R>) -> Self {
+        Self {
+            parent: parent.clone(),
+            local: PidAllocator::new(),
+        }
+    }
+
+    /// Attempts to allocate a PID in the namespace, consuming one PID in the parent as well.
+    ///
+    /// Returns `None` if either the parent or the local allocator has no free PID left.
+    pub fn allocate(&self) -> Option<NamespacedPid<ORDER, W, R>> {
+        let parent = self.parent.allocate()?;
+        let local = self.local.allocate()?;
+        Some(NamespacedPid { local, parent })
+    }
+
+    /// Returns the parent allocator this namespace consumes PIDs from.
+    pub fn parent(&self) -> &PidAllocator<ORDER, W, R> {
+        &self.parent
+    }
+
+    /// Returns the allocator that hands out this namespace's local PIDs.
+    pub fn local(&self) -> &PidAllocator<ORDER, W, R> {
+        &self.local
+    }
+
+    /// Checks whether a given local PID is currently allocated in this namespace.
+    pub fn contains(&self, number: usize) -> bool {
+        self.local.contains(number)
+    }
+}
+
+/// A handle to a PID allocated from a [`PidNamespace`].
+///
+/// Dereferences to the local PID number. When dropped, both the local and the parent PID are recycled.
+#[derive(Debug)]
+pub struct NamespacedPid<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    local: Pid<ORDER, W, R>,
+    parent: Pid<ORDER, W, R>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> NamespacedPid<ORDER, W, R> {
+    /// Returns the PID number as seen from inside the namespace.
+    pub fn local(&self) -> usize {
+        *self.local
+    }
+
+    /// Returns the PID number as seen from the parent allocator.
+    pub fn parent(&self) -> usize {
+        *self.parent
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for NamespacedPid<ORDER, W, R> {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.local
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Borrow<usize>
+    for NamespacedPid<ORDER, W, R>
+{
+    fn borrow(&self) -> &usize {
+        &self.local
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> AsRef<usize> for NamespacedPid<ORDER, W, R> {
+    fn as_ref(&self) -> &usize {
+        &self.local
+    }
+}
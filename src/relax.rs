@@ -0,0 +1,55 @@
+//! Strategies for waiting on the allocator's lock while another thread holds it.
+//!
+//! Every allocator type takes a relax strategy as its last generic parameter, which decides what
+//! a thread does between attempts to take the lock. The default, [`Spin`], busy-waits with a CPU
+//! spin-loop hint, which gives the lowest latency but keeps the core busy. Where that wastes power,
+//! select another strategy, e.g. [`Yield`] on `std` targets, or any type implementing
+//! [`RelaxStrategy`]:
+//!
+//! ```
+//! use pid_allocator::{relax::RelaxStrategy, PidAllocator};
+//!
+//! #[derive(Debug, Default, Clone)]
+//! struct Wfe;
+//!
+//! impl RelaxStrategy for Wfe {
+//!     fn relax() {
+//!         // e.g. wait for an event on ARM
+//!         core::hint::spin_loop();
+//!     }
+//! }
+//!
+//! let allocator = PidAllocator::<8>::with_relax::<Wfe>();
+//! let pid = allocator.allocate().expect("Failed to allocate PID");
+//! assert_eq!(*pid, 0);
+//! ```
+//!
+//! Deriving `Debug`, `Default` and `Clone` on a custom strategy keeps those traits available on the allocator.
+
+pub use spin::relax::RelaxStrategy;
+
+/// Busy-waits with [`core::hint::spin_loop`] between attempts. This is the default strategy.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Spin;
+
+impl RelaxStrategy for Spin {
+    #[inline(always)]
+    fn relax() {
+        core::hint::spin_loop();
+    }
+}
+
+/// Yields the current thread to the OS scheduler between attempts.
+///
+/// This strategy is only available with the `std` feature enabled.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Yield;
+
+#[cfg(feature = "std")]
+impl RelaxStrategy for Yield {
+    #[inline(always)]
+    fn relax() {
+        std::thread::yield_now();
+    }
+}
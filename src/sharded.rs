@@ -0,0 +1,210 @@
+use alloc::boxed::Box;
+use core::{
+    borrow::Borrow,
+    fmt,
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    allocator::Pid,
+    relax::{RelaxStrategy, Spin},
+    word::BitWord,
+    PidAllocator,
+};
+
+/// A PID allocator that partitions its PID space into `SHARDS` independent sub-allocators.
+///
+/// Each shard is a [`PidAllocator`] with its own lock, so threads allocating from different shards
+/// never contend with each other. Shard `i` owns the PIDs `i * PidAllocator::<ORDER, W>::CAPACITY`
+/// up to (but excluding) `(i + 1) * PidAllocator::<ORDER, W>::CAPACITY`, which means a PID number
+/// alone identifies the shard it must be recycled into.
+///
+/// [`ShardedPidAllocator::allocate`] spreads allocations across shards in round-robin order, unless a
+/// selector installed with [`ShardedPidAllocator::set_shard_selector`] picks the shard instead, while
+/// [`ShardedPidAllocator::allocate_on`] lets callers route to a shard of their choice, e.g. using a CPU id.
+/// If the chosen shard is exhausted, the remaining shards are tried in order.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::ShardedPidAllocator;
+///
+/// let allocator = ShardedPidAllocator::<4, 2>::new();
+/// assert_eq!(ShardedPidAllocator::<4, 2>::CAPACITY, 4 * 2 * usize::BITS as usize);
+///
+/// let pid = allocator.allocate_on(3).expect("Failed to allocate PID");
+/// assert_eq!(pid.shard(), 3);
+/// assert!(allocator.contains(*pid));
+/// ```
+pub struct ShardedPidAllocator<
+    const SHARDS: usize,
+    const ORDER: usize,
+    W: BitWord = usize,
+    R: RelaxStrategy = Spin,
+> {
+    shards: [PidAllocator<ORDER, W, R>; SHARDS],
+    next_shard: AtomicUsize,
+    selector: Option<Box<dyn Fn() -> usize + Send + Sync>>,
+}
+
+impl<const SHARDS: usize, const ORDER: usize, W: BitWord, R: RelaxStrategy>
+    ShardedPidAllocator<SHARDS, ORDER, W, R>
+{
+    /// The number of PIDs managed by a single shard.
+    pub const SHARD_CAPACITY: usize = PidAllocator::<ORDER, W>::CAPACITY;
+
+    /// The total number of PIDs this allocator can manage across all shards.
+    ///
+    /// Like [`PidAllocator::CAPACITY`], this fails to compile rather than wrapping if it overflows `usize`.
+    pub const CAPACITY: usize = match SHARDS.checked_mul(Self::SHARD_CAPACITY) {
+        Some(capacity) => capacity,
+        None => panic!("ShardedPidAllocator capacity SHARDS * SHARD_CAPACITY overflows usize"),
+    };
+
+    /// Creates a new sharded allocator with `SHARDS` empty shards.
+    pub fn new() -> Self {
+        Self {
+            shards: core::array::from_fn(|_| PidAllocator::new()),
+            next_shard: AtomicUsize::new(0),
+            selector: None,
+        }
+    }
+
+    /// Installs a function that picks the preferred shard for every call to [`ShardedPidAllocator::allocate`].
+    ///
+    /// The selector typically returns the id of the current CPU, so that each CPU allocates from its own
+    /// shard and never contends with the others. Its result is reduced modulo `SHARDS`, and the other
+    /// shards are still tried when the selected one is exhausted. Without a selector, shards are chosen
+    /// in round-robin order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pid_allocator::ShardedPidAllocator;
+    ///
+    /// let mut allocator = ShardedPidAllocator::<4, 1>::new();
+    /// allocator.set_shard_selector(|| 2);
+    ///
+    /// let pid = allocator.allocate().expect("Failed to allocate PID");
+    /// assert_eq!(pid.shard(), 2);
+    /// ```
+    pub fn set_shard_selector(&mut self, selector: impl Fn() -> usize + Send + Sync + 'static) {
+        self.selector = Some(Box::new(selector));
+    }
+
+    /// Attempts to allocate a new PID, starting from the shard picked by the selector, or from the next
+    /// shard in round-robin order if none is installed. Returns `None` only if every shard is exhausted.
+    pub fn allocate(&self) -> Option<ShardedPid<ORDER, W, R>> {
+        let shard = match &self.selector {
+            Some(selector) => selector(),
+            None => self.next_shard.fetch_add(1, Ordering::Relaxed),
+        };
+        self.allocate_on(shard)
+    }
+
+    /// Attempts to allocate a new PID, preferring the shard selected by `hint`.
+    ///
+    /// `hint` is reduced modulo `SHARDS`, so any per-CPU or per-thread identifier can be passed directly.
+    /// If the preferred shard is exhausted, the remaining shards are tried in order.
+    pub fn allocate_on(&self, hint: usize) -> Option<ShardedPid<ORDER, W, R>> {
+        if SHARDS == 0 {
+            return None;
+        }
+
+        let preferred = hint % SHARDS;
+        (0..SHARDS)
+            .map(|offset| (preferred + offset) % SHARDS)
+            .find_map(|shard| {
+                self.shards[shard].allocate().map(|pid| ShardedPid {
+                    number: shard * Self::SHARD_CAPACITY + *pid,
+                    shard,
+                    pid,
+                })
+            })
+    }
+
+    /// Checks whether a given PID is currently allocated in the shard that owns it.
+    pub fn contains(&self, number: usize) -> bool {
+        let (shard, local) = Self::locate(number);
+        shard < SHARDS && self.shards[shard].contains(local)
+    }
+
+    /// Recycles a PID by number through the shard that owns it, reporting whether it was actually freed.
+    ///
+    /// See [`PidAllocator::try_recycle`] for the caveats of recycling numbers held by live handles.
+    pub fn try_recycle(&self, number: usize) -> bool {
+        let (shard, local) = Self::locate(number);
+        shard < SHARDS && self.shards[shard].try_recycle(local)
+    }
+
+    /// Splits a global PID number into its shard index and the number local to that shard.
+    fn locate(number: usize) -> (usize, usize) {
+        if Self::SHARD_CAPACITY == 0 {
+            return (SHARDS, 0);
+        }
+        (number / Self::SHARD_CAPACITY, number % Self::SHARD_CAPACITY)
+    }
+}
+
+impl<const SHARDS: usize, const ORDER: usize, W: BitWord, R: RelaxStrategy + fmt::Debug> fmt::Debug
+    for ShardedPidAllocator<SHARDS, ORDER, W, R>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedPidAllocator")
+            .field("shards", &self.shards)
+            .field("next_shard", &self.next_shard)
+            .field("selector", &self.selector.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+impl<const SHARDS: usize, const ORDER: usize, W: BitWord, R: RelaxStrategy> Default
+    for ShardedPidAllocator<SHARDS, ORDER, W, R>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A handle to a PID allocated from a [`ShardedPidAllocator`].
+///
+/// Dereferences to the global PID number. When dropped, the PID is recycled into the shard it came from.
+#[derive(Debug)]
+pub struct ShardedPid<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    number: usize,
+    shard: usize,
+    pid: Pid<ORDER, W, R>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> ShardedPid<ORDER, W, R> {
+    /// Returns the index of the shard this PID was allocated from.
+    pub fn shard(&self) -> usize {
+        self.shard
+    }
+
+    /// Returns the PID number local to its shard.
+    pub fn local(&self) -> usize {
+        *self.pid
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Deref for ShardedPid<ORDER, W, R> {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Borrow<usize> for ShardedPid<ORDER, W, R> {
+    fn borrow(&self) -> &usize {
+        &self.number
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> AsRef<usize> for ShardedPid<ORDER, W, R> {
+    fn as_ref(&self) -> &usize {
+        &self.number
+    }
+}
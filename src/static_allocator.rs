@@ -0,0 +1,86 @@
+use spin::mutex::SpinMutex;
+
+use crate::{
+    allocator::{BorrowedPid, PidAllocatorInner},
+    relax::{RelaxStrategy, Spin},
+    word::BitWord,
+};
+
+/// A PID allocator that can be constructed at compile time, e.g. as a `static`.
+///
+/// [`PidAllocator::new`](crate::PidAllocator::new) places its state in an `Arc`, which needs a heap and
+/// therefore cannot initialize a `static`. `StaticPidAllocator` keeps its state inline in a plain
+/// `SpinMutex` and offers a `const fn new`, so it is usable before the global allocator is ready,
+/// e.g. early in a kernel's boot. Its handles are [`BorrowedPid`]s that borrow the allocator, which
+/// for a `static` means they are `'static` and can be stored anywhere.
+///
+/// # Interrupt context
+///
+/// `allocate`, `contains`, `try_recycle` and dropping a [`BorrowedPid`] never touch the heap: the
+/// state is inline, handles hold a plain reference instead of an `Arc`, and none of these paths
+/// grows a collection, since a `StaticPidAllocator` has no quarantine or recycle hook. They do not
+/// panic either, except for the debug assertion that catches a PID being freed twice. This makes
+/// them suitable for interrupt handlers, provided the handler cannot interrupt code that already
+/// holds the allocator's lock on the same core, which would spin forever.
+///
+/// # Examples
+///
+/// ```
+/// use pid_allocator::StaticPidAllocator;
+///
+/// static PIDS: StaticPidAllocator<32> = StaticPidAllocator::new();
+///
+/// let pid = PIDS.allocate().expect("Failed to allocate PID");
+/// assert!(PIDS.contains(*pid));
+/// drop(pid);
+/// assert!(!PIDS.contains(0));
+/// ```
+#[derive(Debug)]
+pub struct StaticPidAllocator<const ORDER: usize, W: BitWord = usize, R: RelaxStrategy = Spin> {
+    inner: SpinMutex<PidAllocatorInner<ORDER, W>, R>,
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> StaticPidAllocator<ORDER, W, R> {
+    /// The total number of PIDs this allocator can manage, equal to `ORDER * W::BITS`.
+    pub const CAPACITY: usize = PidAllocatorInner::<ORDER, W>::CAPACITY;
+
+    /// Creates a new, empty allocator. This is a `const fn`, so it can initialize a `static`.
+    pub const fn new() -> Self {
+        Self {
+            inner: SpinMutex::new(PidAllocatorInner::new()),
+        }
+    }
+
+    /// Attempts to allocate the lowest free PID. Returns `None` if all PIDs are already allocated.
+    pub fn allocate(&self) -> Option<BorrowedPid<'_, ORDER, W, R>> {
+        let number = self.inner.lock().allocate()?;
+        Some(BorrowedPid {
+            number,
+            allocator: &self.inner,
+        })
+    }
+
+    /// Checks whether a given PID is currently allocated.
+    pub fn contains(&self, number: usize) -> bool {
+        self.inner.lock().contains(number)
+    }
+
+    /// Returns the number of currently allocated PIDs.
+    pub fn allocated_count(&self) -> usize {
+        self.inner.lock().allocated_count()
+    }
+
+    /// Recycles a PID by number, reporting whether it was actually freed.
+    ///
+    /// See [`PidAllocator::try_recycle`](crate::PidAllocator::try_recycle) for the caveats of
+    /// recycling numbers held by live handles.
+    pub fn try_recycle(&self, number: usize) -> bool {
+        self.inner.lock().try_recycle(number)
+    }
+}
+
+impl<const ORDER: usize, W: BitWord, R: RelaxStrategy> Default for StaticPidAllocator<ORDER, W, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
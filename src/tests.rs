@@ -1,6 +1,19 @@
-use alloc::vec::Vec;
+use alloc::{string::ToString, vec::Vec};
+use core::{
+    borrow::Borrow,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Barrier, Mutex},
+    thread,
+    time::Duration,
+};
 
-use crate::PidAllocator;
+use crate::{
+    allocator::PidAllocatorInner, AllocationPolicy, LocalPidAllocator, NonZeroPid, PidAllocator, PidCache, PidError, PidNamespace,
+    RangeStatus, ShardedPidAllocator, StaticPidAllocator,
+};
 
 const ORDER: usize = 64;
 
@@ -73,4 +86,2355 @@ const ORDER: usize = 64;
             !allocator.contains(unallocated_pid),
             "Unallocated PID should not be recognized as allocated"
         );
-    }
\ No newline at end of file
+    }
+
+    #[test]
+    fn test_allocate_with_hint() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let hinted = allocator
+            .allocate_with_hint(10)
+            .expect("Failed to allocate hinted PID");
+        assert_eq!(*hinted, 10, "A free hinted PID should be returned as-is");
+
+        let fallback = allocator
+            .allocate_with_hint(10)
+            .expect("Failed to allocate fallback PID");
+        assert_ne!(*fallback, 10, "A taken hint should fall back to another PID");
+        assert!(allocator.contains(*fallback));
+    }
+
+    #[test]
+    fn test_allocate_with_hint_out_of_range() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator
+            .allocate_with_hint(ORDER * usize::BITS as usize)
+            .expect("Out-of-range hint should fall back to a valid PID");
+        assert!(allocator.contains(*pid));
+    }
+
+    #[test]
+    fn test_pid_borrow_as_usize_key() {
+        fn lookup<K: Borrow<usize>>(
+            map: &HashMap<usize, &'static str>,
+            key: &K,
+        ) -> Option<&'static str> {
+            map.get(key.borrow()).copied()
+        }
+
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+
+        let mut map = HashMap::new();
+        map.insert(*pid, "worker");
+
+        assert_eq!(map.get(pid.borrow()), Some(&"worker"));
+        assert_eq!(map.get(pid.as_ref()), Some(&"worker"));
+        assert_eq!(lookup(&map, &pid), Some("worker"));
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pids: Vec<_> = (0..100).filter_map(|_| allocator.allocate()).collect();
+        // Punch a few holes so the bitmap is not just a prefix of ones.
+        let pids: Vec<_> = pids
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, pid)| (index % 3 != 0).then_some(pid))
+            .collect();
+
+        let bytes = allocator.as_bytes();
+        assert_eq!(bytes.len(), ORDER * core::mem::size_of::<usize>());
+
+        let restored = PidAllocator::<ORDER>::from_bytes(&bytes).expect("Failed to import bytes");
+        for number in 0..ORDER * usize::BITS as usize {
+            assert_eq!(
+                allocator.contains(number),
+                restored.contains(number),
+                "Restored allocator differs at PID {}",
+                number
+            );
+        }
+        assert_eq!(restored.as_bytes(), bytes);
+        drop(pids);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_wrong_length() {
+        let bytes = [0u8; ORDER * core::mem::size_of::<usize>() - 1];
+        assert!(PidAllocator::<ORDER>::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn local_pid_allocate_unique() {
+        let allocator = LocalPidAllocator::<ORDER>::new();
+        let pid1 = allocator.allocate().expect("Failed to allocate PID 1");
+        let pid2 = allocator.allocate().expect("Failed to allocate PID 2");
+        assert_ne!(*pid1, *pid2, "Allocated PIDs should be unique");
+    }
+
+    #[test]
+    fn local_pid_recycle_and_reallocate() {
+        let allocator = LocalPidAllocator::<ORDER>::new();
+
+        {
+            let _pid = allocator.allocate().expect("Failed to allocate PID");
+        }
+
+        let pids: Vec<_> = (0..ORDER * usize::BITS as usize)
+            .map(|_| {
+                allocator
+                    .allocate()
+                    .expect("Failed to allocate a new PID after recycling")
+            })
+            .collect();
+
+        assert_eq!(pids.len(), ORDER * usize::BITS as usize);
+        assert!(allocator.allocate().is_none(), "Allocator should be full");
+    }
+
+    #[test]
+    fn local_contains_and_recycle() {
+        let allocator = LocalPidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        let pid_value = *pid;
+        assert!(allocator.contains(pid_value));
+
+        core::mem::drop(pid);
+        assert!(!allocator.contains(pid_value));
+        assert!(!allocator.contains(ORDER * usize::BITS as usize * 2));
+    }
+
+    #[test]
+    fn local_allocate_with_hint() {
+        let allocator = LocalPidAllocator::<ORDER>::new();
+        let hinted = allocator.allocate_with_hint(3).expect("Failed to allocate PID");
+        assert_eq!(*hinted, 3);
+        let fallback = allocator.allocate_with_hint(3).expect("Failed to allocate PID");
+        assert_ne!(*fallback, 3);
+    }
+
+    #[test]
+    fn test_try_recycle_allocated_pid() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        let number = *pid;
+        core::mem::forget(pid);
+
+        assert!(allocator.try_recycle(number), "Allocated PID should be freed");
+        assert!(!allocator.contains(number));
+    }
+
+    #[test]
+    fn test_try_recycle_free_pid() {
+        let allocator = PidAllocator::<ORDER>::new();
+        assert!(!allocator.try_recycle(0), "Free PID should not be freed again");
+    }
+
+    #[test]
+    fn test_try_recycle_out_of_range() {
+        let allocator = PidAllocator::<ORDER>::new();
+        assert!(!allocator.try_recycle(ORDER * usize::BITS as usize));
+        assert!(!allocator.try_recycle(usize::MAX));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "double free")]
+    fn test_double_free_panics_in_debug() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        assert!(allocator.try_recycle(*pid));
+        // Dropping the handle frees the same number a second time.
+        drop(pid);
+    }
+
+    #[test]
+    fn test_u32_layers_capacity() {
+        assert_eq!(PidAllocator::<ORDER, u32>::CAPACITY, ORDER * 32);
+        assert_eq!(PidAllocator::<ORDER>::CAPACITY, ORDER * usize::BITS as usize);
+    }
+
+    #[test]
+    fn test_u32_layers_allocate_and_recycle() {
+        let allocator = PidAllocator::<2, u32>::new();
+        let pids: Vec<_> = (0..64)
+            .map(|_| allocator.allocate().expect("Failed to allocate PID"))
+            .collect();
+        assert!(allocator.allocate().is_none(), "Allocator should be full at 2 * 32 PIDs");
+        assert_eq!(*pids[33], 33);
+
+        let number = *pids[40];
+        drop(pids);
+        assert!(!allocator.contains(number));
+        assert_eq!(allocator.as_bytes().len(), 2 * 4);
+    }
+
+    #[test]
+    fn test_u128_layers_allocate_and_recycle() {
+        assert_eq!(PidAllocator::<ORDER, u128>::CAPACITY, ORDER * 128);
+
+        let allocator = PidAllocator::<2, u128>::new();
+        let pids: Vec<_> = allocator.allocations().collect();
+        assert_eq!(pids.len(), 256, "Allocator should be full at 2 * 128 PIDs");
+        assert_eq!(*pids[200], 200);
+
+        let number = *pids[130];
+        drop(pids);
+        assert!(!allocator.contains(number));
+        assert_eq!(*allocator.allocate_at(number).unwrap(), number);
+        assert_eq!(allocator.as_bytes().len(), 2 * 16);
+    }
+
+    #[test]
+    fn test_allocate_with_runs_closure_after_recycle() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let freed = AtomicUsize::new(usize::MAX);
+        let was_free = AtomicUsize::new(0);
+
+        let guard = allocator
+            .allocate_with(|number| {
+                freed.store(number, Ordering::SeqCst);
+                was_free.store(!allocator.contains(number) as usize, Ordering::SeqCst);
+            })
+            .expect("Failed to allocate PID");
+        let number = *guard;
+        assert!(allocator.contains(number));
+        assert_eq!(freed.load(Ordering::SeqCst), usize::MAX, "Closure must not run early");
+
+        drop(guard);
+        assert_eq!(freed.load(Ordering::SeqCst), number);
+        assert_eq!(
+            was_free.load(Ordering::SeqCst),
+            1,
+            "PID should already be free when the closure runs"
+        );
+    }
+
+    #[test]
+    fn test_allocations_drains_pool() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pids: Vec<_> = allocator.allocations().collect();
+        assert_eq!(pids.len(), PidAllocator::<ORDER>::CAPACITY);
+
+        let unique: HashSet<usize> = pids.iter().map(|pid| **pid).collect();
+        assert_eq!(unique.len(), pids.len(), "Allocated PIDs should be unique");
+        assert!(allocator.allocate().is_none());
+    }
+
+    #[test]
+    fn test_allocate_blocking_waits_for_free_pid() {
+        let allocator = PidAllocator::<1>::new();
+        let mut pids: Vec<_> = allocator.allocations().collect();
+        assert!(allocator.allocate().is_none(), "Pool should be exhausted");
+
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| *allocator.allocate_blocking());
+
+            thread::sleep(Duration::from_millis(50));
+            assert!(!waiter.is_finished(), "Waiter should block while the pool is full");
+
+            let pid = pids.pop().expect("Pool should not be empty");
+            let freed = *pid;
+            drop(pid);
+
+            let number = waiter.join().expect("Waiter thread panicked");
+            assert_eq!(number, freed, "Waiter should receive the freed PID");
+        });
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_allocate_async_resolves_after_free() {
+        let allocator = alloc::sync::Arc::new(PidAllocator::<1>::new());
+        let mut pids: Vec<_> = allocator.allocations().collect();
+
+        let waiter = {
+            let allocator = allocator.clone();
+            tokio::spawn(async move { *allocator.allocate_async().await })
+        };
+
+        // Let the waiter observe the exhausted pool and register itself.
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished(), "Waiter should be pending while the pool is full");
+
+        let pid = pids.pop().expect("Pool should not be empty");
+        let freed = *pid;
+        drop(pid);
+
+        let number = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("allocate_async did not complete after a PID was freed")
+            .expect("Waiter task panicked");
+        assert_eq!(number, freed);
+    }
+
+    #[test]
+    fn test_peek_next_matches_allocate() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let _first = allocator.allocate().expect("Failed to allocate PID");
+        let second = allocator.allocate().expect("Failed to allocate PID");
+        let freed = *second;
+        drop(second);
+
+        let peeked = allocator.peek_next();
+        assert_eq!(peeked, Some(freed), "peek_next should report the lowest free PID");
+        assert_eq!(peeked, allocator.peek_next(), "peek_next should not allocate");
+
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        assert_eq!(Some(*pid), peeked);
+    }
+
+    #[test]
+    fn test_peek_next_when_full() {
+        let allocator = PidAllocator::<1>::new();
+        let _pids: Vec<_> = allocator.allocations().collect();
+        assert_eq!(allocator.peek_next(), None);
+    }
+
+    #[test]
+    fn test_sharded_unique_across_shards() {
+        const SHARDS: usize = 4;
+        let allocator = ShardedPidAllocator::<SHARDS, 1>::new();
+
+        let pids: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..SHARDS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..usize::BITS)
+                            .map(|_| allocator.allocate().expect("Failed to allocate PID"))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Worker thread panicked"))
+                .collect()
+        });
+
+        assert_eq!(pids.len(), ShardedPidAllocator::<SHARDS, 1>::CAPACITY);
+        let unique: HashSet<usize> = pids.iter().map(|pid| **pid).collect();
+        assert_eq!(unique.len(), pids.len(), "Sharded PIDs should be unique");
+        assert!(allocator.allocate().is_none(), "All shards should be exhausted");
+    }
+
+    #[test]
+    fn test_sharded_recycles_into_owning_shard() {
+        let allocator = ShardedPidAllocator::<4, 1>::new();
+        let pid = allocator.allocate_on(2).expect("Failed to allocate PID");
+        let number = *pid;
+        assert_eq!(pid.shard(), 2);
+        assert_eq!(number, 2 * usize::BITS as usize + pid.local());
+        assert!(allocator.contains(number));
+
+        drop(pid);
+        assert!(!allocator.contains(number));
+        assert!(!allocator.contains(ShardedPidAllocator::<4, 1>::CAPACITY));
+    }
+
+    #[test]
+    fn test_sharded_overflows_into_other_shards() {
+        let allocator = ShardedPidAllocator::<2, 1>::new();
+        let first: Vec<_> = (0..usize::BITS)
+            .map(|_| allocator.allocate_on(0).expect("Failed to allocate PID"))
+            .collect();
+        assert!(first.iter().all(|pid| pid.shard() == 0));
+
+        let overflow = allocator.allocate_on(0).expect("Should overflow into shard 1");
+        assert_eq!(overflow.shard(), 1);
+    }
+
+    #[test]
+    fn test_pid_cache_unique_across_threads() {
+        const THREADS: usize = 4;
+        const PER_THREAD: usize = 100;
+        let allocator = PidAllocator::<ORDER>::new();
+        let barrier = Barrier::new(THREADS);
+
+        let numbers: Vec<usize> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let cache = PidCache::new(&allocator, 16);
+                        let pids: Vec<_> = (0..PER_THREAD)
+                            .map(|_| cache.allocate().expect("Failed to allocate PID"))
+                            .collect();
+                        let numbers: Vec<usize> = pids.iter().map(|pid| **pid).collect();
+                        // Hold every handle until all threads have allocated.
+                        barrier.wait();
+                        numbers
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Worker thread panicked"))
+                .collect()
+        });
+
+        let unique: HashSet<usize> = numbers.iter().copied().collect();
+        assert_eq!(unique.len(), THREADS * PER_THREAD, "Cached PIDs should be unique");
+        assert_eq!(
+            allocator.allocations().collect::<Vec<_>>().len(),
+            PidAllocator::<ORDER>::CAPACITY,
+            "Dropping the caches should return every PID to the global pool"
+        );
+    }
+
+    #[test]
+    fn test_pid_cache_serves_from_stock() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let cache = PidCache::new(&allocator, 8);
+
+        let pid = cache.allocate().expect("Failed to allocate PID");
+        assert_eq!(*pid, 0, "The lowest PID of the batch should be handed out first");
+        assert_eq!(cache.cached(), 7);
+        assert!(allocator.contains(7), "Cached PIDs are claimed from the global pool");
+
+        drop(pid);
+        assert_eq!(cache.cached(), 8);
+        cache.flush();
+        assert_eq!(cache.cached(), 0);
+        assert_eq!(allocator.peek_next(), Some(0));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_contention_count_under_stress() {
+        const THREADS: usize = 8;
+        let allocator = PidAllocator::<ORDER>::new();
+        let barrier = Barrier::new(THREADS);
+
+        // Contention depends on scheduling, so retry a few rounds before giving up.
+        for _ in 0..10 {
+            thread::scope(|scope| {
+                for _ in 0..THREADS {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        for _ in 0..20_000 {
+                            drop(allocator.allocate());
+                        }
+                    });
+                }
+            });
+            if allocator.contention_count() > 0 {
+                break;
+            }
+        }
+
+        assert!(
+            allocator.contention_count() > 0,
+            "Concurrent allocations should contend for the lock"
+        );
+    }
+
+    #[test]
+    fn test_contains_checked_allocated() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        assert_eq!(allocator.contains_checked(*pid), Ok(true));
+    }
+
+    #[test]
+    fn test_contains_checked_free() {
+        let allocator = PidAllocator::<ORDER>::new();
+        assert_eq!(allocator.contains_checked(PidAllocator::<ORDER>::CAPACITY - 1), Ok(false));
+    }
+
+    #[test]
+    fn test_contains_checked_out_of_range() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let number = PidAllocator::<ORDER>::CAPACITY;
+        assert_eq!(allocator.contains_checked(number), Err(PidError::OutOfRange(number)));
+        assert!(!allocator.contains(number), "contains should keep returning false");
+    }
+
+    #[test]
+    fn test_recycle_all_empties_allocator() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let numbers: Vec<usize> = allocator
+            .allocations()
+            .take(200)
+            .map(|pid| {
+                let number = *pid;
+                core::mem::forget(pid);
+                number
+            })
+            .collect();
+        assert!(numbers.iter().all(|&number| allocator.contains(number)));
+
+        allocator.recycle_all(
+            numbers
+                .iter()
+                .copied()
+                .chain([PidAllocator::<ORDER>::CAPACITY, usize::MAX]),
+        );
+
+        assert!(
+            (0..PidAllocator::<ORDER>::CAPACITY).all(|number| !allocator.contains(number)),
+            "Allocator should be empty after recycle_all"
+        );
+    }
+
+    #[test]
+    fn test_fork_is_independent() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+
+        let fork = allocator.fork();
+        assert!(fork.contains(*pid), "Fork should start with the same allocations");
+
+        let forked: Vec<_> = (0..10)
+            .map(|_| fork.allocate().expect("Failed to allocate PID on fork"))
+            .collect();
+        assert!(forked.iter().all(|pid| !allocator.contains(**pid)));
+
+        let number = *pid;
+        drop(pid);
+        assert!(!allocator.contains(number));
+        assert!(fork.contains(number), "Recycling on the original must not affect the fork");
+    }
+
+    #[test]
+    fn test_clone_shares_state() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let shared = allocator.clone();
+        let pid = shared.allocate().expect("Failed to allocate PID");
+        assert!(allocator.contains(*pid));
+    }
+
+    #[test]
+    fn test_assert_valid_accepts_supported_orders() {
+        const _: () = PidAllocator::<0>::assert_valid();
+        const _: () = PidAllocator::<1>::assert_valid();
+        const _: () = PidAllocator::<ORDER>::assert_valid();
+        const _: () = PidAllocator::<{ usize::BITS as usize }, u8>::assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "ORDER must not exceed usize::BITS")]
+    fn test_assert_valid_rejects_oversized_order_at_runtime() {
+        PidAllocator::<{ usize::BITS as usize + 1 }>::assert_valid();
+    }
+
+    #[test]
+    fn test_allocate_nonzero_never_zero() {
+        let allocator = PidAllocator::<1>::new();
+        let pids: Vec<_> = (1..usize::BITS)
+            .map(|_| allocator.allocate_nonzero().expect("Failed to allocate PID"))
+            .collect();
+        assert!(pids.iter().all(|pid| pid.get() != 0));
+        assert!(allocator.allocate_nonzero().is_none(), "Only PID 0 should remain");
+
+        let zero = allocator.allocate().expect("PID 0 should still be available");
+        assert_eq!(*zero, 0);
+
+        let number = pids[0].get();
+        drop(pids);
+        assert!(!allocator.contains(number), "NonZeroPid should recycle on drop");
+    }
+
+    #[test]
+    fn test_nonzero_pid_niche() {
+        assert_eq!(
+            core::mem::size_of::<Option<NonZeroPid<ORDER>>>(),
+            core::mem::size_of::<NonZeroPid<ORDER>>()
+        );
+    }
+
+    #[test]
+    fn test_fragmented_reallocation_stays_unique() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let mut pids: Vec<_> = allocator.allocations().collect();
+
+        // Free a scattered set of PIDs spread over many layers, highest first.
+        let freed: HashSet<usize> = (0..PidAllocator::<ORDER>::CAPACITY)
+            .rev()
+            .filter(|number| number % 7 == 3)
+            .collect();
+        pids.retain(|pid| !freed.contains(&**pid));
+
+        let reallocated: Vec<_> = allocator.allocations().collect();
+        let numbers: HashSet<usize> = reallocated.iter().map(|pid| **pid).collect();
+        assert_eq!(numbers.len(), reallocated.len(), "Reallocated PIDs should be unique");
+        assert_eq!(numbers, freed, "Exactly the freed PIDs should be handed out again");
+
+        assert!(
+            reallocated.windows(2).all(|pair| *pair[0] < *pair[1]),
+            "Freed PIDs should be handed out lowest first"
+        );
+        assert_eq!(*reallocated[0], 3);
+    }
+
+    #[test]
+    fn test_inner_allocated_count_matches_popcount() {
+        let words: [u64; 4] = [0, u64::MAX, 0xdead_beef_0000_0001, 0x8000_0000_0000_0000];
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let inner = PidAllocatorInner::<4, u64>::from_bytes(&bytes).expect("Invalid length");
+
+        let expected: u32 = words.iter().map(|word| word.count_ones()).sum();
+        assert_eq!(inner.allocated_count(), expected as usize);
+        assert_eq!(PidAllocatorInner::<4, u64>::new().allocated_count(), 0);
+    }
+
+    #[test]
+    fn test_allocated_count_tracks_allocations() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pids: Vec<_> = (0..150).filter_map(|_| allocator.allocate()).collect();
+        assert_eq!(allocator.allocated_count(), 150);
+        drop(pids);
+        assert_eq!(allocator.allocated_count(), 0);
+    }
+
+    #[test]
+    fn test_with_max_caps_allocation() {
+        let allocator = PidAllocator::<32>::with_max(100);
+        let pids: Vec<_> = (0..100)
+            .map(|_| allocator.allocate().expect("PIDs below max should be available"))
+            .collect();
+        assert!(allocator.allocate().is_none(), "The 101st allocation should fail");
+        assert!(allocator.contains(50));
+        assert!(!allocator.contains(100));
+        assert!(allocator.allocate_at(100).is_none());
+        assert_eq!(allocator.peek_next(), None);
+
+        drop(pids);
+        assert_eq!(allocator.allocated_count(), 0);
+        assert_eq!(*allocator.allocate().expect("Failed to allocate PID"), 0);
+    }
+
+    /// A small deterministic RNG so random allocation can be tested reproducibly.
+    #[cfg(feature = "rand_core")]
+    struct SplitMix64(u64);
+
+    #[cfg(feature = "rand_core")]
+    impl rand_core::RngCore for SplitMix64 {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest)
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_allocate_random_is_reproducible_and_unique() {
+        let allocate_all = |seed| {
+            let allocator = PidAllocator::<2>::new();
+            let mut rng = SplitMix64(seed);
+            let pids: Vec<_> = (0..PidAllocator::<2>::CAPACITY)
+                .map(|_| {
+                    allocator
+                        .allocate_random(&mut rng)
+                        .expect("Failed to allocate PID")
+                })
+                .collect();
+            assert!(allocator.allocate_random(&mut rng).is_none());
+            pids.iter().map(|pid| **pid).collect::<Vec<usize>>()
+        };
+
+        let numbers = allocate_all(7);
+        assert_eq!(numbers, allocate_all(7), "Same seed should give the same sequence");
+
+        let unique: HashSet<usize> = numbers.iter().copied().collect();
+        assert_eq!(unique.len(), PidAllocator::<2>::CAPACITY);
+        assert!(
+            numbers.windows(2).any(|pair| pair[1] != pair[0] + 1),
+            "Random allocation should not be sequential"
+        );
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_allocate_random_respects_max() {
+        let allocator = PidAllocator::<ORDER>::with_max(10);
+        let mut rng = SplitMix64(1);
+        let pids: Vec<_> = (0..10)
+            .map(|_| allocator.allocate_random(&mut rng).expect("Failed to allocate PID"))
+            .collect();
+        assert!(pids.iter().all(|pid| **pid < 10));
+        assert!(allocator.allocate_random(&mut rng).is_none());
+        assert_eq!(allocator.failed_allocations(), 1, "A full random allocation counts as a failure");
+    }
+
+    #[test]
+    fn test_namespace_allocates_in_parent_and_child() {
+        let parent = PidAllocator::<ORDER>::new();
+        let _init = parent.allocate().expect("Failed to allocate PID");
+        let namespace = PidNamespace::new(&parent);
+
+        let pid = namespace.allocate().expect("Failed to allocate PID");
+        let (local, parent_number) = (pid.local(), pid.parent());
+        assert_eq!(*pid, local);
+        assert!(namespace.contains(local), "Child should see the local PID");
+        assert!(parent.contains(parent_number), "Parent should see the parent PID");
+        assert!(!parent.contains(parent_number + 1));
+
+        drop(pid);
+        assert!(!namespace.contains(local), "Local PID should be freed on drop");
+        assert!(!parent.contains(parent_number), "Parent PID should be freed on drop");
+    }
+
+    #[test]
+    fn test_namespace_fails_cleanly_when_parent_is_full() {
+        let parent = PidAllocator::<1, u8>::new();
+        let _taken: Vec<_> = (0..7).map(|_| parent.allocate().unwrap()).collect();
+        let namespace = PidNamespace::new(&parent);
+
+        let pid = namespace.allocate().expect("Failed to allocate PID");
+        assert_eq!(pid.parent(), 7);
+        assert!(namespace.allocate().is_none(), "Parent is exhausted");
+        assert_eq!(namespace.local().allocated_count(), 1, "No local PID should leak");
+    }
+
+    #[test]
+    fn test_quarantine_delays_reuse() {
+        let depth = 3;
+        let allocator = PidAllocator::<ORDER>::with_quarantine(depth);
+        let first = allocator.allocate().expect("Failed to allocate PID");
+        let number = *first;
+        drop(first);
+
+        assert!(!allocator.contains(number), "Quarantined PID should not count as allocated");
+        assert!(allocator.allocate_at(number).is_none(), "Quarantined PID must not be reused");
+
+        let others: Vec<_> = (0..depth)
+            .map(|_| allocator.allocate().expect("Failed to allocate PID"))
+            .collect();
+        assert!(
+            others.iter().all(|pid| **pid != number),
+            "PID was reused before {} other allocations",
+            depth
+        );
+        assert_eq!(allocator.allocated_count(), depth);
+
+        let reused = allocator.allocate().expect("Failed to allocate PID");
+        assert_eq!(*reused, number, "PID should be free again after the quarantine");
+    }
+
+    #[test]
+    fn test_allocate_or_reuse_freed_pulls_from_quarantine() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(16);
+        let mut pids: Vec<_> = allocator.allocations().collect();
+        assert_eq!(pids.len(), 8);
+
+        let number = *pids.remove(3);
+        assert!(!allocator.try_recycle(number), "Quarantined PID is already recycled");
+        assert!(allocator.allocate().is_none(), "Plain allocate must respect the quarantine");
+
+        let pid = allocator
+            .allocate_or_reuse_freed()
+            .expect("Oldest quarantined PID should be released");
+        assert_eq!(*pid, number);
+        assert!(allocator.allocate_or_reuse_freed().is_none(), "Nothing left to reuse");
+        assert_eq!(allocator.check_invariants(), Ok(()));
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn test_defmt_format_is_implemented() {
+        fn assert_format<T: defmt::Format>(_: &T) {}
+
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        assert_format(&pid);
+        assert_format(&allocator);
+    }
+
+    #[test]
+    fn test_debug_bitmap_renders_lowest_pid_first() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let _pids: Vec<_> = [0, 2, 5]
+            .iter()
+            .map(|&number| allocator.allocate_at(number).expect("Failed to allocate PID"))
+            .collect();
+
+        let rendered = allocator.debug_bitmap().to_string();
+        assert!(rendered.starts_with("101001"), "Unexpected bitmap: {}", rendered);
+
+        let rows: Vec<&str> = rendered.lines().collect();
+        assert_eq!(rows.len(), ORDER);
+        assert!(rows.iter().all(|row| row.len() == usize::BITS as usize));
+        assert_eq!(rendered.matches('1').count(), 3);
+    }
+
+    #[test]
+    fn test_allocate_n_rolls_back_on_failure() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let _held: Vec<_> = (0..10).map(|_| allocator.allocate().unwrap()).collect();
+        let before = allocator.as_bytes();
+
+        let available = PidAllocator::<ORDER>::CAPACITY - 10;
+        assert!(allocator.allocate_n(available + 1).is_none());
+        assert_eq!(allocator.allocated_count(), 10);
+        assert_eq!(allocator.as_bytes(), before, "Bitmap should be unchanged");
+
+        let batch = allocator
+            .allocate_n(available)
+            .expect("Full-capacity batch should still succeed");
+        assert_eq!(batch.len(), available);
+        assert!(allocator.allocate().is_none());
+    }
+
+    #[test]
+    fn test_allocate_n_rollback_restores_quarantine() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(1);
+        let mut pids = Vec::from(allocator.allocate_n(6).expect("Failed to allocate PIDs"));
+        pids.truncate(5);
+        let before = allocator.as_bytes();
+
+        // PID 5 leaves the quarantine after the first allocation of the batch, which then fails.
+        assert!(allocator.allocate_n(4).is_none());
+        assert_eq!(allocator.as_bytes(), before, "PID 5 should be quarantined again");
+        assert!(!allocator.contains(5));
+        assert_eq!(allocator.check_invariants(), Ok(()));
+
+        let batch = allocator.allocate_n(3).expect("Failed to allocate PIDs");
+        let numbers: Vec<usize> = batch.iter().map(|pid| **pid).collect();
+        assert_eq!(numbers, [6, 5, 7]);
+    }
+
+    #[test]
+    fn test_pid_into_usize_keeps_pid_allocated() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        let number: usize = pid.into();
+
+        assert!(allocator.contains(number), "Converted PID should stay allocated");
+        assert_eq!(alloc::sync::Arc::strong_count(&allocator.inner), 1, "Handle should release its Arc");
+
+        assert!(allocator.try_recycle(number));
+        assert!(!allocator.contains(number));
+    }
+
+    #[test]
+    fn test_first_free_bit_on_crafted_bitmaps() {
+        let first_free = |layers: [u8; 3]| {
+            PidAllocatorInner::<3, u8>::from_bytes(&layers)
+                .expect("Bitmap should have the right length")
+                .first_free_bit()
+        };
+
+        assert_eq!(first_free([0x00, 0x00, 0x00]), Some(0));
+        assert_eq!(first_free([0b0000_0111, 0x00, 0x00]), Some(3));
+        assert_eq!(first_free([0b1111_1110, 0x00, 0x00]), Some(0));
+        assert_eq!(first_free([0xff, 0b0001_0111, 0x00]), Some(11));
+        assert_eq!(first_free([0xff, 0xff, 0b0111_1111]), Some(23));
+        assert_eq!(first_free([0xff, 0xff, 0xff]), None);
+    }
+
+    #[test]
+    fn test_first_free_bit_respects_max_and_hint() {
+        let mut inner = PidAllocatorInner::<3, u8>::with_max(10);
+        for _ in 0..10 {
+            assert!(inner.allocate().is_some());
+        }
+        assert_eq!(inner.first_free_bit(), None, "PIDs at or above max are unavailable");
+
+        inner.recycle(4);
+        assert_eq!(inner.first_free_bit(), Some(4), "Recycling must lower the hint");
+    }
+
+    #[test]
+    fn test_allocate_in_keeps_subsystems_in_their_lanes() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let half = PidAllocator::<ORDER>::CAPACITY / 2;
+
+        let lower: Vec<_> = core::iter::from_fn(|| allocator.allocate_in(0..half)).collect();
+        assert_eq!(lower.len(), half);
+        assert!(lower.iter().all(|pid| **pid < half), "Lower caller crossed into the upper half");
+
+        let upper: Vec<_> = core::iter::from_fn(|| allocator.allocate_in(half..usize::MAX)).collect();
+        assert_eq!(upper.len(), PidAllocator::<ORDER>::CAPACITY - half);
+        assert!(upper.iter().all(|pid| **pid >= half), "Upper caller crossed into the lower half");
+
+        assert!(allocator.allocate().is_none());
+    }
+
+    #[test]
+    fn test_allocate_in_handles_unaligned_and_empty_ranges() {
+        let allocator = PidAllocator::<4, u8>::with_max(20);
+        let pid = allocator.allocate_in(5..13).expect("Failed to allocate PID");
+        assert_eq!(*pid, 5);
+        let _rest: Vec<_> = (6..13).map(|_| allocator.allocate_in(5..13).unwrap()).collect();
+        assert!(allocator.allocate_in(5..13).is_none());
+        assert_eq!(allocator.peek_next(), Some(0), "PIDs outside the range stay free");
+
+        assert!(allocator.allocate_in(7..7).is_none());
+        assert!(allocator.allocate_in(20..32).is_none(), "Range is clamped to max");
+    }
+
+    #[test]
+    fn test_merge_disjoint_allocators() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let other = PidAllocator::<ORDER>::new();
+        let _evens: Vec<_> = (0..200).step_by(2).map(|n| allocator.allocate_at(n).unwrap()).collect();
+        let _odds: Vec<_> = (1..200).step_by(2).map(|n| other.allocate_at(n).unwrap()).collect();
+
+        assert_eq!(allocator.merge(&other), Ok(()));
+        assert_eq!(allocator.allocated_count(), 200);
+        assert!((0..200).all(|n| allocator.contains(n)));
+        assert_eq!(allocator.peek_next(), Some(200));
+        assert_eq!(other.allocated_count(), 100, "Source allocator should be unchanged");
+    }
+
+    #[test]
+    fn test_merge_reports_conflict_without_changes() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let other = PidAllocator::<ORDER>::new();
+        let _mine = allocator.allocate_at(70).unwrap();
+        let _theirs: Vec<_> = [3, 70, 90].iter().map(|&n| other.allocate_at(n).unwrap()).collect();
+        let before = allocator.as_bytes();
+
+        assert_eq!(allocator.merge(&other), Err(70));
+        assert_eq!(allocator.as_bytes(), before, "A failed merge must not import anything");
+
+        let capped = PidAllocator::<ORDER>::with_max(80);
+        assert_eq!(capped.merge(&other), Err(90), "PIDs beyond max cannot be imported");
+    }
+
+    #[test]
+    fn test_recompute_top_repairs_corrupted_summary() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let layer = usize::BITS as usize;
+        let _full: Vec<_> = (0..layer).map(|_| allocator.allocate().unwrap()).collect();
+        let _partial = allocator.allocate_at(3 * layer).unwrap();
+
+        allocator.inner.lock().top_layer = usize::MAX;
+        allocator.recompute_top();
+        assert_eq!(allocator.inner.lock().top_layer, 0b1, "Only the first layer is full");
+
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+        assert_eq!(*pid, layer);
+        drop(pid);
+
+        // Filling a layer and freeing a PID in it keep the summary in sync incrementally.
+        let _rest: Vec<_> = (layer + 1..3 * layer).map(|n| allocator.allocate_at(n).unwrap()).collect();
+        let _gap = allocator.allocate_at(layer).unwrap();
+        assert_eq!(allocator.inner.lock().top_layer, 0b111);
+    }
+
+    #[test]
+    fn test_check_invariants_on_valid_state() {
+        let allocator = PidAllocator::<2, u8>::with_quarantine(2);
+        assert_eq!(allocator.check_invariants(), Ok(()));
+
+        let mut pids: Vec<_> = allocator.allocations().collect();
+        assert_eq!(allocator.check_invariants(), Ok(()));
+        pids.truncate(5);
+        assert_eq!(allocator.check_invariants(), Ok(()));
+        let _more: Vec<_> = (0..3)
+            .map(|_| allocator.allocate_or_reuse_freed().unwrap())
+            .collect();
+        assert_eq!(allocator.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_check_invariants_reports_corruption() {
+        let mut inner = PidAllocatorInner::<2, u8>::new();
+        inner.top_layer = 0b100;
+        assert_eq!(inner.check_invariants(), Err("top_layer has bits set beyond ORDER"));
+
+        inner.top_layer = 0b01;
+        assert_eq!(
+            inner.check_invariants(),
+            Err("top_layer does not match the full bottom layers")
+        );
+
+        inner.top_layer = 0;
+        for _ in 0..8 {
+            inner.allocate();
+        }
+        inner.top_layer = 0;
+        assert_eq!(
+            inner.check_invariants(),
+            Err("top_layer does not match the full bottom layers")
+        );
+        inner.recompute_top();
+        assert_eq!(inner.check_invariants(), Ok(()));
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn test_ffi_allocate_recycle_round_trip() {
+        use crate::ffi::{
+            pid_allocator_allocate, pid_allocator_free, pid_allocator_new, pid_allocator_recycle,
+            PID_ALLOCATOR_EXHAUSTED,
+        };
+
+        let allocator = pid_allocator_new();
+        unsafe {
+            assert_eq!(pid_allocator_allocate(allocator), 0);
+            assert_eq!(pid_allocator_allocate(allocator), 1);
+            assert!(pid_allocator_recycle(allocator, 0));
+            assert!(!pid_allocator_recycle(allocator, 0), "PID 0 is already free");
+            assert_eq!(pid_allocator_allocate(allocator), 0, "Recycled PID should be reused");
+
+            assert_eq!(pid_allocator_allocate(core::ptr::null()), PID_ALLOCATOR_EXHAUSTED);
+            assert!(!pid_allocator_recycle(core::ptr::null(), 0));
+            pid_allocator_free(allocator);
+            pid_allocator_free(core::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_borrowed_pid_frees_on_drop_without_arc_clone() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate_borrowed().expect("Failed to allocate PID");
+        assert_eq!(*pid, 0);
+        assert!(allocator.contains(0));
+        assert_eq!(
+            alloc::sync::Arc::strong_count(&allocator.inner),
+            1,
+            "A borrowed handle should not clone the Arc"
+        );
+
+        drop(pid);
+        assert!(!allocator.contains(0), "PID should be recycled on drop");
+        assert_eq!(allocator.allocate_borrowed().map(|pid| *pid), Some(0));
+    }
+
+    #[test]
+    fn test_allocate_nonzero_bypasses_quarantine_for_zero() {
+        let allocator = PidAllocator::<ORDER>::with_quarantine(4);
+        let pid = allocator.allocate_nonzero().expect("Failed to allocate PID");
+        assert_eq!(pid.get(), 1);
+        assert_eq!(*allocator.allocate().unwrap(), 0, "PID 0 was never handed out");
+        assert_eq!(allocator.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_free_bits_is_complement_of_allocated() {
+        let bitmap = [0b1010_0101, 0xff, 0x00, 0b1000_0000];
+        let inner = PidAllocatorInner::<4, u8>::from_bytes(&bitmap).unwrap();
+
+        let free: Vec<usize> = inner.free_bits().collect();
+        let expected: Vec<usize> = (0..32).filter(|&n| !inner.contains(n)).collect();
+        assert_eq!(free, expected);
+        assert_eq!(&free[..4], &[1, 3, 4, 6]);
+        assert_eq!(free.last(), Some(&30));
+
+        let mut capped = PidAllocatorInner::<4, u8>::with_max(20);
+        assert!(capped.allocate_at(17));
+        let free: Vec<usize> = capped.free_bits().collect();
+        assert_eq!(free.len(), 19);
+        assert!(free.iter().all(|&n| n < 20 && n != 17));
+    }
+
+    /// A relax strategy that yields to the OS scheduler, standing in for `relax::Yield`.
+    #[derive(Debug)]
+    struct YieldNow;
+
+    impl crate::relax::RelaxStrategy for YieldNow {
+        fn relax() {
+            thread::yield_now();
+        }
+    }
+
+    fn exercise_under_contention<R: crate::relax::RelaxStrategy>(allocator: &PidAllocator<ORDER, usize, R>) {
+        const THREADS: usize = 4;
+        let seen = std::sync::Mutex::new(HashSet::new());
+        let barrier = Barrier::new(THREADS);
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    let pids: Vec<_> = (0..64).map(|_| allocator.allocate().unwrap()).collect();
+                    let duplicates = {
+                        let mut seen = seen.lock().unwrap();
+                        pids.iter().filter(|pid| !seen.insert(***pid)).count()
+                    };
+                    // Keep every PID alive until all threads have recorded theirs.
+                    barrier.wait();
+                    assert_eq!(duplicates, 0, "PIDs were allocated twice");
+                });
+            }
+        });
+        assert_eq!(allocator.allocated_count(), 0, "Every PID should be recycled");
+        assert_eq!(seen.into_inner().unwrap().len(), THREADS * 64);
+    }
+
+    #[test]
+    fn test_allocator_works_with_both_relax_strategies() {
+        exercise_under_contention(&PidAllocator::<ORDER>::with_relax::<crate::relax::Spin>());
+        exercise_under_contention(&PidAllocator::<ORDER>::with_relax::<YieldNow>());
+        #[cfg(feature = "std")]
+        exercise_under_contention(&PidAllocator::<ORDER>::with_relax::<crate::relax::Yield>());
+    }
+
+    #[test]
+    fn test_pid_compares_with_usize() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let _zero = allocator.allocate().unwrap();
+        let pid = allocator.allocate().expect("Failed to allocate PID");
+
+        assert!(pid == 1);
+        assert!(pid != 2);
+        assert!(pid < 10);
+        assert!(pid >= 1);
+        assert!(pid <= 1);
+        assert!(pid > 0);
+
+        assert!(1 == pid);
+        assert!(2 != pid);
+        assert!(0 < pid);
+        assert!(10 > pid);
+    }
+
+    #[test]
+    fn test_static_allocator_recycles_on_drop() {
+        static PIDS: StaticPidAllocator<4> = StaticPidAllocator::new();
+
+        let first = PIDS.allocate().expect("Failed to allocate PID");
+        let second = PIDS.allocate().expect("Failed to allocate PID");
+        assert_eq!((*first, *second), (0, 1));
+        assert_eq!(PIDS.allocated_count(), 2);
+
+        // Handles borrow the static, so they can be moved into a spawned thread.
+        let number = thread::spawn(move || *second).join().unwrap();
+        assert_eq!(number, 1);
+        assert!(!PIDS.contains(1), "PID should be recycled when its handle is dropped");
+
+        drop(first);
+        assert_eq!(PIDS.allocated_count(), 0);
+        assert_eq!(PIDS.allocate().map(|pid| *pid), Some(0));
+    }
+
+    #[test]
+    fn test_try_allocate_nonblocking_returns_when_locked() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let (locked, done) = (Barrier::new(2), Barrier::new(2));
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _guard = allocator.inner.lock();
+                locked.wait();
+                done.wait();
+            });
+
+            locked.wait();
+            let result = allocator.try_allocate_nonblocking();
+            assert_eq!(result.map(|pid| pid.map(|pid| *pid)), Err(PidError::WouldBlock));
+            done.wait();
+        });
+
+        let _pids: Vec<_> = allocator.allocations().collect();
+        assert!(matches!(allocator.try_allocate_nonblocking(), Ok(None)), "Exhaustion is not contention");
+    }
+
+    #[test]
+    fn test_longest_free_run_and_fragmentation() {
+        let allocator = PidAllocator::<ORDER>::new();
+        assert_eq!(allocator.longest_free_run(), PidAllocator::<ORDER>::CAPACITY);
+        assert_eq!(allocator.fragmentation(), 0.0);
+
+        let _checkerboard: Vec<_> = (0..PidAllocator::<ORDER>::CAPACITY)
+            .step_by(2)
+            .map(|number| allocator.allocate_at(number).unwrap())
+            .collect();
+        assert_eq!(allocator.longest_free_run(), 1);
+        assert!(allocator.fragmentation() > 0.99);
+
+        let full: Vec<_> = allocator.allocations().collect();
+        assert_eq!(allocator.longest_free_run(), 0);
+        assert_eq!(allocator.fragmentation(), 0.0);
+        drop(full);
+    }
+
+    #[test]
+    fn test_longest_free_run_spans_layers() {
+        let allocator = PidAllocator::<4, u8>::new();
+        let _low = allocator.allocate_at(5).unwrap();
+        let _high = allocator.allocate_at(27).unwrap();
+        assert_eq!(allocator.longest_free_run(), 21, "Run 6..27 crosses two layer boundaries");
+
+        let capped = PidAllocator::<4, u8>::with_max(10);
+        assert_eq!(capped.longest_free_run(), 10);
+    }
+
+    #[test]
+    fn test_sharded_selector_prefers_selected_shard() {
+        let mut allocator = ShardedPidAllocator::<4, 1>::new();
+        allocator.set_shard_selector(|| 2);
+        let shard_capacity = ShardedPidAllocator::<4, 1>::SHARD_CAPACITY;
+
+        let first: Vec<_> = (0..shard_capacity)
+            .map(|_| allocator.allocate().unwrap())
+            .collect();
+        assert!(first.iter().all(|pid| pid.shard() == 2));
+        assert!(first
+            .iter()
+            .all(|pid| (2 * shard_capacity..3 * shard_capacity).contains(&**pid)));
+
+        let overflow = allocator.allocate().unwrap();
+        assert_eq!(overflow.shard(), 3, "Overflow should move on to the next shard");
+
+        drop(first);
+        assert_eq!(allocator.allocate().unwrap().shard(), 2);
+    }
+
+    #[test]
+    fn test_contains_range_fully_allocated() {
+        let allocator = PidAllocator::<4, u8>::new();
+        let _block: Vec<_> = (6..20).map(|n| allocator.allocate_at(n).unwrap()).collect();
+        assert_eq!(allocator.contains_range(6..20), RangeStatus::Allocated);
+        assert_eq!(allocator.contains_range(8..16), RangeStatus::Allocated);
+    }
+
+    #[test]
+    fn test_contains_range_fully_free() {
+        let allocator = PidAllocator::<4, u8>::new();
+        let _low = allocator.allocate_at(5).unwrap();
+        let _high = allocator.allocate_at(27).unwrap();
+        assert_eq!(allocator.contains_range(6..27), RangeStatus::Free);
+        assert_eq!(allocator.contains_range(28..100), RangeStatus::Free);
+        assert_eq!(allocator.contains_range(10..10), RangeStatus::Free);
+    }
+
+    #[test]
+    fn test_contains_range_partially_allocated() {
+        let allocator = PidAllocator::<4, u8>::with_quarantine(1);
+        let _pid = allocator.allocate_at(12).unwrap();
+        assert_eq!(allocator.contains_range(0..16), RangeStatus::Mixed);
+        assert_eq!(allocator.contains_range(12..13), RangeStatus::Allocated);
+        assert_eq!(allocator.contains_range(30..40), RangeStatus::Free, "Range beyond capacity counts as free");
+
+        let recycled = allocator.allocate_at(13).unwrap();
+        drop(recycled);
+        assert_eq!(allocator.contains_range(12..14), RangeStatus::Mixed, "Quarantined PIDs count as free");
+    }
+
+    #[test]
+    fn test_policy_lowest_first() {
+        let allocator = PidAllocator::<4, u8>::with_policy(AllocationPolicy::LowestFirst);
+        let pids: Vec<_> = (0..3).map(|_| allocator.allocate().unwrap()).collect();
+        assert_eq!(pids.iter().map(|pid| **pid).collect::<Vec<_>>(), [0, 1, 2]);
+        drop(pids);
+        assert_eq!(*allocator.allocate().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_policy_highest_first() {
+        let allocator = PidAllocator::<4, u8>::with_policy(AllocationPolicy::HighestFirst);
+        let pids: Vec<_> = (0..3).map(|_| allocator.allocate().unwrap()).collect();
+        assert_eq!(pids.iter().map(|pid| **pid).collect::<Vec<_>>(), [31, 30, 29]);
+
+        let capped = PidAllocator::<4, u8>::with_max(10);
+        capped.set_policy(AllocationPolicy::HighestFirst);
+        assert_eq!(capped.peek_next(), Some(9));
+        assert_eq!(*capped.allocate().unwrap(), 9);
+    }
+
+    #[test]
+    fn test_policy_round_robin() {
+        let allocator = PidAllocator::<1, u8>::with_policy(AllocationPolicy::RoundRobin);
+        let first = allocator.allocate().unwrap();
+        let second = allocator.allocate().unwrap();
+        assert_eq!((*first, *second), (0, 1));
+        drop(first);
+        assert_eq!(*allocator.allocate().unwrap(), 2, "Recycled PIDs wait for a full cycle");
+
+        let rest: Vec<_> = (3..8).map(|_| allocator.allocate().unwrap()).collect();
+        assert_eq!(rest.iter().map(|pid| **pid).collect::<Vec<_>>(), [3, 4, 5, 6, 7]);
+        assert_eq!(*allocator.allocate().unwrap(), 0, "The search wraps around at the end");
+        drop(second);
+    }
+
+    #[test]
+    fn test_policy_random() {
+        let allocator = PidAllocator::<ORDER>::with_policy(AllocationPolicy::Random);
+        assert_eq!(allocator.policy(), AllocationPolicy::Random);
+
+        let mut pids = Vec::new();
+        for _ in 0..8 {
+            let next = allocator.peek_next();
+            let pid = allocator.allocate().unwrap();
+            assert_eq!(next, Some(*pid), "peek_next must agree with the random policy");
+            pids.push(pid);
+        }
+        let numbers: Vec<usize> = pids.iter().map(|pid| **pid).collect();
+        let unique: HashSet<_> = numbers.iter().collect();
+        assert_eq!(unique.len(), numbers.len());
+        assert_ne!(numbers, (0..8).collect::<Vec<_>>(), "Random order should not be sequential");
+
+        let all: Vec<_> = allocator.allocations().collect();
+        assert_eq!(all.len() + pids.len(), PidAllocator::<ORDER>::CAPACITY);
+        assert!(allocator.allocate().is_none());
+    }
+
+    #[test]
+    fn test_pid_recycled_when_holder_thread_panics() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let worker = allocator.clone();
+        let result = thread::spawn(move || {
+            let pid = worker.allocate().unwrap();
+            assert_eq!(*pid, 0);
+            panic!("deliberate panic while holding {}", *pid);
+        })
+        .join();
+
+        assert!(result.is_err(), "The worker thread should have panicked");
+        assert!(!allocator.contains(0), "Unwinding must recycle the PID");
+        assert_eq!(*allocator.allocate().unwrap(), 0, "The lock must not be left held");
+    }
+
+    #[test]
+    fn test_lock_usable_after_panicking_drop_callback() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = allocator
+                .allocate_with(|number| panic!("deliberate panic recycling {number}"))
+                .unwrap();
+        }));
+
+        assert!(result.is_err(), "The drop callback should have panicked");
+        assert_eq!(allocator.allocated_count(), 0);
+        assert!(allocator.check_invariants().is_ok());
+        assert_eq!(*allocator.allocate().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reservation_commit_keeps_pid() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let reservation = allocator.reserve().unwrap();
+        let number = *reservation;
+        assert!(allocator.contains(number), "A reserved PID is allocated");
+        assert_ne!(*allocator.allocate().unwrap(), number);
+
+        let pid = reservation.commit();
+        assert_eq!(*pid, number);
+        assert!(allocator.contains(number), "Committing keeps the PID");
+        drop(pid);
+        assert!(!allocator.contains(number));
+    }
+
+    #[test]
+    fn test_reservation_abort_and_drop_free_pid() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let reservation = allocator.reserve().unwrap();
+        let number = *reservation;
+        reservation.abort();
+        assert!(!allocator.contains(number), "Aborting frees the PID");
+
+        let setup = |fail: bool| -> Option<crate::Pid<ORDER>> {
+            let reservation = allocator.reserve()?;
+            if fail {
+                return None;
+            }
+            Some(reservation.commit())
+        };
+        assert!(setup(true).is_none());
+        assert_eq!(allocator.allocated_count(), 0, "Dropping a reservation frees the PID");
+        assert!(setup(false).is_some_and(|pid| allocator.contains(*pid)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_to_set_matches_scattered_allocations() {
+        let allocator = PidAllocator::<4, u8>::with_quarantine(1);
+        let expected: HashSet<usize> = [0, 7, 8, 15, 19, 31].into_iter().collect();
+        let _pids: Vec<_> = expected.iter().map(|&n| allocator.allocate_at(n).unwrap()).collect();
+        drop(allocator.allocate_at(20).unwrap());
+
+        assert_eq!(allocator.to_set(), expected, "Quarantined PIDs are not part of the snapshot");
+        assert!(PidAllocator::<4, u8>::new().to_set().is_empty());
+    }
+
+    #[test]
+    fn test_collect_rebuilds_allocator_state() {
+        let numbers: HashSet<usize> = [0, 3, 64, 65, 511, 200].into_iter().collect();
+        let allocator: PidAllocator<ORDER> = numbers.iter().copied().collect();
+        assert!(numbers.iter().all(|&n| allocator.contains(n)));
+        assert_eq!(allocator.allocated_count(), numbers.len());
+        assert!(allocator.check_invariants().is_ok());
+        assert_eq!(*allocator.allocate().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_extend_skips_duplicates_and_out_of_range() {
+        let mut allocator = PidAllocator::<1, u8>::with_max(6);
+        allocator.extend([0, 1, 2]);
+        allocator.extend([2, 5, 6, 8, usize::MAX]);
+        assert_eq!(allocator.allocated_count(), 4);
+        assert!((0..=2).chain([5]).all(|n| allocator.contains(n)));
+        assert!(!allocator.contains(6), "Numbers at or above max are skipped");
+
+        assert!(allocator.try_recycle(5), "Imported PIDs are freed explicitly");
+        assert_eq!(allocator.allocated_count(), 3);
+    }
+
+    #[test]
+    fn test_recycle_hook_sees_drop_order() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let recycled = Arc::new(Mutex::new(Vec::new()));
+        let log = recycled.clone();
+        allocator.set_recycle_hook(move |number| log.lock().unwrap().push(number));
+
+        let pids: Vec<_> = (0..5).map(|_| allocator.allocate().unwrap()).collect();
+        let mut pids = pids.into_iter().map(Some).collect::<Vec<_>>();
+        for index in [3, 0, 4, 1, 2] {
+            pids[index].take();
+        }
+        let explicit = allocator.allocate().unwrap();
+        let number = usize::from(explicit);
+        assert!(allocator.try_recycle(number));
+
+        assert_eq!(*recycled.lock().unwrap(), [3, 0, 4, 1, 2, number]);
+    }
+
+    #[test]
+    fn test_allocate_into_fixed_buffer() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let mut buffer: [Option<crate::Pid<ORDER>>; 16] = Default::default();
+        assert_eq!(allocator.allocate_into(&mut buffer), 16);
+        let unique: HashSet<usize> = buffer.iter().flatten().map(|pid| **pid).collect();
+        assert_eq!(unique.len(), 16);
+        assert_eq!(allocator.allocated_count(), 16);
+
+        // Refilling recycles the previous handles first, so the same PIDs come back.
+        assert_eq!(allocator.allocate_into(&mut buffer), 16);
+        assert_eq!(allocator.allocated_count(), 16);
+        let refilled: HashSet<usize> = buffer.iter().flatten().map(|pid| **pid).collect();
+        assert_eq!(refilled, unique);
+
+        let small = PidAllocator::<1, u8>::new();
+        let mut buffer: [Option<crate::Pid<1, u8>>; 12] = Default::default();
+        assert_eq!(small.allocate_into(&mut buffer), 8);
+        assert!(buffer[8..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_zero_order_is_empty_and_full() {
+        let allocator = PidAllocator::<0>::new();
+        assert_eq!(PidAllocator::<0>::CAPACITY, 0);
+        assert_eq!(allocator.capacity(), 0);
+        assert!(allocator.is_empty());
+        assert!(allocator.is_full());
+        assert!(allocator.allocate().is_none());
+        assert!(allocator.allocate_at(0).is_none());
+        assert!(allocator.allocate_n(1).is_none());
+        assert!(allocator.allocate_n(0).is_some_and(|pids| pids.is_empty()));
+        assert_eq!(allocator.peek_next(), None);
+        assert_eq!(allocator.allocated_count(), 0);
+        assert_eq!(allocator.longest_free_run(), 0);
+        assert!(!allocator.contains(0));
+        assert!(!allocator.try_recycle(0));
+        assert!(allocator.as_bytes().is_empty());
+        assert!(allocator.check_invariants().is_ok());
+        assert!(PidAllocator::<0>::from_bytes(&[]).is_some());
+
+        allocator.set_policy(AllocationPolicy::Random);
+        assert!(allocator.allocate().is_none());
+        allocator.set_policy(AllocationPolicy::HighestFirst);
+        assert!(allocator.allocate().is_none());
+    }
+
+    #[test]
+    fn test_capacity_at_the_order_ceiling() {
+        // The widest valid layout: usize::BITS layers of u128. On a 16-bit target this is
+        // 16 * 128 = 2048 PIDs, far below u16::MAX, so PID numbers can never wrap.
+        const WIDEST: usize = PidAllocator::<{ usize::BITS as usize }, u128>::CAPACITY;
+        assert_eq!(WIDEST, usize::BITS as usize * 128);
+        assert!(16usize.checked_mul(128).is_some_and(|capacity| capacity <= u16::MAX as usize));
+
+        let allocator = PidAllocator::<{ usize::BITS as usize }, u128>::with_policy(AllocationPolicy::HighestFirst);
+        assert_eq!(*allocator.allocate().unwrap(), WIDEST - 1);
+        assert_eq!(allocator.capacity(), WIDEST);
+    }
+
+    #[test]
+    fn test_lifetime_counters_accumulate() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let pids = allocator.allocate_n(8).unwrap();
+        assert_eq!((allocator.total_allocations(), allocator.failed_allocations()), (8, 0));
+
+        assert!(allocator.allocate().is_none());
+        assert!(allocator.allocate_n(2).is_none());
+        assert_eq!((allocator.total_allocations(), allocator.failed_allocations()), (8, 2));
+
+        drop(pids);
+        assert_eq!(allocator.total_allocations(), 8, "Recycling must not lower the total");
+        let again: Vec<_> = allocator.allocations().collect();
+        assert_eq!(again.len(), 8);
+        assert_eq!(allocator.total_allocations(), 16);
+        assert_eq!(allocator.failed_allocations(), 3, "The iterator ends on one failed attempt");
+        assert_eq!(allocator.allocated_count(), 8);
+    }
+
+    #[test]
+    fn test_drain_allocated_returns_live_set_and_resets() {
+        let allocator = PidAllocator::<ORDER>::with_quarantine(2);
+        let expected = [1, 63, 64, 130, 400];
+        for &number in &expected {
+            core::mem::forget(allocator.allocate_at(number).unwrap());
+        }
+        allocator.recycle_all([400]);
+        assert!(!allocator.contains(400), "PID 400 is now quarantined");
+
+        assert_eq!(allocator.drain_allocated(), expected[..4]);
+        assert_eq!(allocator.allocated_count(), 0);
+        assert_eq!(allocator.longest_free_run(), PidAllocator::<ORDER>::CAPACITY, "The quarantine is cleared too");
+        assert!(allocator.check_invariants().is_ok());
+        assert_eq!(*allocator.allocate().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_free_search_jumps_over_full_layers() {
+        let mut inner = PidAllocatorInner::<{ usize::BITS as usize }, u8>::new();
+        let capacity = usize::BITS as usize * 8;
+        (0..capacity).for_each(|number| assert!(inner.import(number)));
+        assert_eq!(inner.first_free_bit(), None, "A completely full top layer yields nothing");
+
+        inner.try_recycle(capacity - 3);
+        inner.try_recycle(0);
+        assert!(inner.import(0), "Re-marking PID 0 keeps the hint at layer 0");
+        assert_eq!(inner.first_free_bit(), Some(capacity - 3));
+        assert!(inner.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn test_pid_recovers_its_allocator() {
+        let pid = PidAllocator::<ORDER>::new().allocate().unwrap();
+        let allocator = pid.allocator();
+        let sibling = allocator.allocate().unwrap();
+        assert_ne!(*pid, *sibling, "The recovered allocator shares the pool");
+        assert_eq!(allocator.allocated_count(), 2);
+
+        drop(pid);
+        assert_eq!(allocator.allocated_count(), 1, "The pool outlives the original allocator handle");
+    }
+
+    /// Counts heap allocations made by the current thread while `FORBID_HEAP` is set.
+    struct CountingAllocator;
+
+    std::thread_local! {
+        static FORBID_HEAP: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+        static FORBIDDEN_ALLOCATIONS: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+    }
+
+    unsafe impl core::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
+            if FORBID_HEAP.try_with(core::cell::Cell::get).unwrap_or(false) {
+                let _ = FORBIDDEN_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            }
+            // SAFETY: Forwarded unchanged to the system allocator.
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: core::alloc::Layout) {
+            // SAFETY: `ptr` was allocated by the system allocator with this layout.
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    /// Runs `f` and returns how many heap allocations it made on this thread.
+    fn heap_allocations_during(f: impl FnOnce()) -> usize {
+        FORBIDDEN_ALLOCATIONS.with(|count| count.set(0));
+        FORBID_HEAP.with(|forbid| forbid.set(true));
+        f();
+        FORBID_HEAP.with(|forbid| forbid.set(false));
+        FORBIDDEN_ALLOCATIONS.with(core::cell::Cell::get)
+    }
+
+    #[test]
+    fn test_static_allocator_never_touches_the_heap() {
+        static PIDS: StaticPidAllocator<4> = StaticPidAllocator::new();
+
+        assert_eq!(heap_allocations_during(|| drop(alloc::boxed::Box::new(1))), 1, "The harness must see allocations");
+        let allocations = heap_allocations_during(|| {
+            let pids = [PIDS.allocate(), PIDS.allocate(), PIDS.allocate()];
+            assert!(pids.iter().all(Option::is_some));
+            assert!(PIDS.contains(1));
+            drop(pids);
+            assert!(!PIDS.try_recycle(1));
+        });
+        assert_eq!(allocations, 0, "allocate, recycle and drop must not allocate");
+        assert_eq!(PIDS.allocated_count(), 0);
+    }
+
+    #[test]
+    fn test_allocate_if_veto_leaves_pid_free() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let mut offered = None;
+        assert!(allocator
+            .allocate_if(|number| {
+                offered = Some(number);
+                number != 0
+            })
+            .is_none());
+        assert_eq!(offered, Some(0));
+        assert!(!allocator.contains(0), "PID 0 must not be consumed by a veto");
+        assert_eq!(allocator.allocated_count(), 0);
+
+        let first = allocator.allocate_if(|_| true).unwrap();
+        assert_eq!(*first, 0);
+        let second = allocator.allocate_if(|number| number != 0).unwrap();
+        assert_eq!(*second, 1);
+
+        let full = PidAllocator::<1, u8>::new();
+        let _all = full.allocate_n(8).unwrap();
+        assert!(full.allocate_if(|_| panic!("pred must not run when full")).is_none());
+    }
+
+    #[test]
+    fn test_pid_batch_numbers_match_handles() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let _gap = allocator.allocate_at(1).unwrap();
+        let batch = allocator.allocate_n(4).unwrap();
+
+        let numbers: Vec<usize> = batch.numbers().collect();
+        let from_handles: Vec<usize> = batch.iter().map(|pid| **pid).collect();
+        assert_eq!(numbers, from_handles);
+        assert_eq!(numbers, [0, 2, 3, 4]);
+        assert_eq!((&batch).into_iter().count(), 4);
+
+        let manual: Vec<usize> = batch.into_numbers().collect();
+        assert_eq!(manual, numbers);
+        assert!(manual.iter().all(|&n| allocator.contains(n)), "into_numbers keeps the PIDs allocated");
+        allocator.recycle_all(manual);
+        assert_eq!(allocator.allocated_count(), 1);
+    }
+
+    #[test]
+    fn test_transfer_moves_pid_between_allocators() {
+        let source = PidAllocator::<ORDER>::new();
+        let dest = PidAllocator::<ORDER>::new();
+        let pid = source.allocate_at(7).unwrap();
+
+        let moved = source.transfer(pid, &dest).unwrap();
+        assert_eq!(*moved, 7);
+        assert!(!source.contains(7), "The source frees the number");
+        assert!(dest.contains(7), "The destination claims the same number");
+        drop(moved);
+        assert!(!dest.contains(7), "The new handle recycles into the destination");
+        assert_eq!(source.allocated_count() + dest.allocated_count(), 0);
+    }
+
+    #[test]
+    fn test_transfer_conflict_returns_original_handle() {
+        let source = PidAllocator::<ORDER>::new();
+        let dest = PidAllocator::<ORDER>::new();
+        let pid = source.allocate_at(3).unwrap();
+        let _taken = dest.allocate_at(3).unwrap();
+
+        let original = source.transfer(pid, &dest).unwrap_err();
+        assert_eq!(*original, 3);
+        assert!(source.contains(3), "The original stays allocated in the source");
+        drop(original);
+        assert!(!source.contains(3), "The original still recycles into the source");
+        assert!(dest.contains(3));
+
+        let foreign = dest.allocate().unwrap();
+        let number = *foreign;
+        let foreign = source.transfer(foreign, &PidAllocator::new()).unwrap_err();
+        assert_eq!(*foreign, number, "A PID from another allocator is rejected");
+    }
+
+    #[test]
+    fn test_clear_range_frees_only_the_span() {
+        let allocator = PidAllocator::<4, u8>::new();
+        let outside: Vec<_> = (0..10).chain(20..32).map(|n| allocator.allocate_at(n).unwrap()).collect();
+        (10..20).for_each(|n| core::mem::forget(allocator.allocate_at(n).unwrap()));
+        assert!(allocator.is_full());
+
+        allocator.clear_range(10..20);
+        assert!((10..20).all(|n| !allocator.contains(n)), "Every PID in the range is freed");
+        assert!(outside.iter().all(|pid| allocator.contains(**pid)), "PIDs outside are untouched");
+        assert_eq!(allocator.allocated_count(), outside.len());
+        assert!(allocator.check_invariants().is_ok(), "top_layer must be repaired");
+        assert_eq!(allocator.peek_next(), Some(10));
+
+        allocator.clear_range(30..usize::MAX);
+        allocator.clear_range(40..50);
+        assert!(!allocator.contains(31) && allocator.contains(29), "The range is clamped to the capacity");
+        assert!(allocator.check_invariants().is_ok());
+        // PIDs 30 and 31 were freed behind their handles' backs; take them over before dropping.
+        outside.into_iter().filter(|pid| **pid >= 30).for_each(|pid| {
+            let _ = usize::from(pid);
+        });
+    }
+
+    #[test]
+    fn test_clear_range_drops_quarantined_pids() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(4);
+        drop(allocator.allocate_at(2).unwrap());
+        allocator.clear_range(0..4);
+        assert!(allocator.check_invariants().is_ok());
+        let pid = allocator.allocate_at(2).expect("PID 2 left the quarantine");
+        let _others: Vec<_> = (0..4).filter_map(|_| allocator.allocate()).collect();
+        assert!(allocator.contains(*pid), "A stale quarantine entry must not free PID 2 later");
+    }
+
+    #[test]
+    fn test_allocation_seq_strictly_increases() {
+        let allocator = PidAllocator::<ORDER>::with_allocation_seq();
+        let first = allocator.allocate_at(40).unwrap();
+        let second = allocator.allocate().unwrap();
+        let third = allocator.allocate_in(10..20).unwrap();
+        let sequences: Vec<u64> = [&first, &second, &third]
+            .iter()
+            .map(|pid| allocator.allocation_seq(***pid).unwrap())
+            .collect();
+        assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]), "{sequences:?}");
+
+        let number = *second;
+        drop(second);
+        assert_eq!(allocator.allocation_seq(number), None, "Freed PIDs have no sequence");
+        let again = allocator.allocate().unwrap();
+        assert_eq!(*again, number);
+        assert!(allocator.allocation_seq(number) > Some(sequences[2]));
+
+        assert_eq!(PidAllocator::<ORDER>::new().allocation_seq(0), None, "Tracking is opt-in");
+    }
+
+    #[test]
+    fn test_contains_pid_tracks_live_handle() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let pid = allocator.allocate().unwrap();
+        assert!(allocator.contains_pid(&pid));
+
+        let other = PidAllocator::<ORDER>::new();
+        assert!(!other.contains_pid(&pid), "The number is looked up in the queried allocator");
+    }
+
+    #[test]
+    fn test_pin_low_skips_pinned_pids_until_unpinned() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let held = allocator.allocate_at(2).unwrap();
+        allocator.pin_low(4);
+        assert_eq!(allocator.allocated_count(), 1, "Pinned PIDs are not counted as allocated");
+        assert!(allocator.contains(2) && !allocator.contains(0));
+
+        let pid = allocator.allocate().unwrap();
+        assert_eq!(*pid, 4);
+        assert!(allocator.allocate_at(1).is_none());
+
+        drop(held);
+        assert!(!allocator.contains(2));
+        assert!(allocator.allocate_at(2).is_none(), "A PID recycled while pinned stays unavailable");
+        allocator.check_invariants().unwrap();
+
+        allocator.unpin_low();
+        let pids: Vec<_> = (0..4).map(|_| allocator.allocate().unwrap()).collect();
+        assert_eq!(pids.iter().map(|pid| **pid).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(allocator.allocated_count(), 5);
+        allocator.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_allocate_retry_succeeds_once_a_pid_is_freed() {
+        let allocator = PidAllocator::<1>::new();
+        let mut pids: Vec<_> = allocator.allocations().collect();
+        let freed = *pids[0];
+
+        thread::scope(|scope| {
+            let pid = pids.remove(0);
+            scope.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                drop(pid);
+            });
+
+            let mut retries = 0;
+            let pid = allocator
+                .allocate_retry(1000, |attempt| {
+                    retries = attempt + 1;
+                    thread::sleep(Duration::from_millis(1 << attempt.min(4)));
+                })
+                .expect("A PID should be freed within the retry budget");
+            assert_eq!(*pid, freed);
+            assert!(retries > 0, "The pool was full on the first attempt");
+        });
+    }
+
+    #[test]
+    fn test_min_and_max_allocated() {
+        let allocator = PidAllocator::<ORDER, u8>::with_quarantine(4);
+        assert_eq!((allocator.min_allocated(), allocator.max_allocated()), (None, None));
+
+        let mut pids: Vec<_> = [3, 17, 200, 64, 1, 255]
+            .into_iter()
+            .map(|number| allocator.allocate_at(number).unwrap())
+            .collect();
+        assert_eq!(allocator.min_allocated(), Some(1));
+        assert_eq!(allocator.max_allocated(), Some(255));
+
+        pids.retain(|pid| **pid != 1 && **pid != 255);
+        assert_eq!(allocator.min_allocated(), Some(3), "Quarantined PIDs are skipped");
+        assert_eq!(allocator.max_allocated(), Some(200));
+
+        pids.clear();
+        assert_eq!((allocator.min_allocated(), allocator.max_allocated()), (None, None));
+    }
+
+    #[test]
+    fn test_allocation_iterators_stay_exhausted() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let mut allocations = allocator.allocations();
+        let mut pids: Vec<_> = allocations.by_ref().collect();
+        assert_eq!(pids.len(), 8);
+
+        drop(pids.pop());
+        for _ in 0..3 {
+            assert!(allocations.next().is_none(), "A fused iterator never resumes");
+        }
+        let _last = allocator.allocate().expect("The freed PID is still allocatable");
+
+        let batch = allocator.allocate_n(0).unwrap();
+        let mut numbers = batch.numbers();
+        assert_eq!(numbers.next(), None);
+        assert_eq!(numbers.next(), None);
+
+        let inner = allocator.inner.lock();
+        let mut free = inner.free_bits();
+        assert_eq!(free.next(), None);
+        assert_eq!(free.next(), None);
+        let mut set = inner.set_bits();
+        assert_eq!(set.by_ref().count(), 8);
+        assert_eq!(set.next(), None);
+    }
+
+    #[test]
+    fn test_snapshot_diff_reports_allocated_and_freed() {
+        let allocator = PidAllocator::<2, u8>::new();
+        let mut pids: Vec<_> = [1, 5, 9, 15]
+            .into_iter()
+            .map(|number| allocator.allocate_at(number).unwrap())
+            .collect();
+        let snapshot = allocator.snapshot();
+        assert_eq!(snapshot.diff(&allocator), (Vec::new(), Vec::new()));
+
+        pids.retain(|pid| **pid != 5 && **pid != 15);
+        pids.push(allocator.allocate_at(12).unwrap());
+        pids.push(allocator.allocate().unwrap());
+        assert_eq!(*pids[3], 0);
+
+        let freed = pids.remove(0);
+        drop(freed);
+        pids.push(allocator.allocate_at(1).unwrap());
+        let (allocated, freed) = snapshot.diff(&allocator);
+        assert_eq!(allocated, [0, 12]);
+        assert_eq!(freed, [5, 15], "PID 1 was freed and reused");
+        assert_eq!(snapshot, snapshot.clone());
+    }
+
+    #[test]
+    fn test_allocate_aligned_returns_multiples_of_align() {
+        let allocator = PidAllocator::<2, u32>::new();
+        let _unaligned: Vec<_> = [0, 9, 16].map(|number| allocator.allocate_at(number).unwrap()).into();
+        let pids: Vec<_> = core::iter::from_fn(|| allocator.allocate_aligned(8)).collect();
+        assert_eq!(pids.iter().map(|pid| **pid).collect::<Vec<_>>(), [8, 24, 32, 40, 48, 56]);
+        assert!(allocator.allocate_aligned(8).is_none(), "Every aligned PID is taken");
+        assert!(allocator.allocate().is_some(), "Unaligned PIDs are still free");
+
+        let wide = PidAllocator::<4, u8>::new();
+        let _first = wide.allocate_aligned(16).unwrap();
+        assert_eq!(*wide.allocate_aligned(16).unwrap(), 16, "Alignment may span several layers");
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn test_allocate_aligned_rejects_non_power_of_two() {
+        let _ = PidAllocator::<ORDER>::new().allocate_aligned(6);
+    }
+
+    #[test]
+    fn test_free_in_range_counts_remaining_pids() {
+        let allocator = PidAllocator::<4, u8>::with_quarantine(8);
+        let mut pids: Vec<_> = (4..12)
+            .step_by(2)
+            .map(|number| allocator.allocate_at(number).unwrap())
+            .collect();
+        assert_eq!(allocator.free_in_range(4..12), 4, "Half the range is allocated");
+        assert_eq!(allocator.free_in_range(5..6), 1);
+        assert_eq!(allocator.free_in_range(6..6), 0);
+
+        pids.pop();
+        assert_eq!(allocator.free_in_range(4..12), 4, "Quarantined PIDs are not free");
+        assert_eq!(allocator.free_in_range(30..40), 2, "The range is clamped to the capacity");
+    }
+
+    #[test]
+    fn test_try_narrow_copies_low_layers() {
+        let allocator = PidAllocator::<4, u8>::with_max(30);
+        let pids: Vec<_> = [0, 3, 15].map(|number| allocator.allocate_at(number).unwrap()).into();
+
+        let narrow = allocator.try_narrow::<2>().expect("Every allocated PID fits in 2 layers");
+        assert_eq!(narrow.allocated_count(), 3);
+        assert!(pids.iter().all(|pid| narrow.contains(**pid)));
+        assert_eq!(narrow.capacity(), 16);
+        narrow.check_invariants().unwrap();
+
+        let _high = allocator.allocate_at(16).unwrap();
+        assert!(allocator.try_narrow::<2>().is_none(), "PID 16 lies beyond 2 layers");
+        assert_eq!(allocator.try_narrow::<3>().unwrap().capacity(), 24);
+        assert_eq!(allocator.try_narrow::<4>().unwrap().capacity(), 30, "The bound carries over");
+    }
+
+    #[test]
+    fn test_lock_guard_runs_steps_atomically() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let held = allocator.allocate_at(0).unwrap();
+        let number = *held;
+        let held = usize::from(held);
+
+        let replacement = thread::scope(|scope| {
+            let mut guard = allocator.lock();
+            let contender = scope.spawn(|| *allocator.allocate_blocking());
+
+            // Swap the held PID for a fresh one; the contender cannot observe the freed PID in between.
+            let replacement = guard.allocate().unwrap();
+            assert!(guard.try_recycle(held));
+            assert!(!guard.allocate_at(replacement), "The guard's own allocation is visible");
+            assert!(!guard.contains(number));
+            assert_eq!((guard.allocated_count(), guard.total_allocations()), (1, 2));
+            assert_eq!(guard.peek_next(), Some(0));
+            drop(guard);
+
+            let contended = contender.join().unwrap();
+            assert_eq!(contended, number, "The contender only runs after the swap");
+            replacement
+        });
+        assert_eq!(replacement, 1);
+        assert!(allocator.contains(replacement), "Numbers from the guard stay allocated");
+        assert!(allocator.try_recycle(replacement));
+        allocator.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_reserve_n_draws_and_frees_the_rest() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let _first = allocator.allocate().unwrap();
+        let mut reservation = allocator.reserve_n(5).unwrap();
+        assert_eq!(allocator.allocated_count(), 6);
+        assert!(allocator.reserve_n(3).is_none(), "Only 2 PIDs are left");
+        assert_eq!(allocator.allocated_count(), 6, "A failed reservation claims nothing");
+
+        let drawn: Vec<_> = (0..3).map(|_| reservation.draw().unwrap()).collect();
+        assert_eq!(drawn.iter().map(|pid| **pid).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(reservation.remaining(), 2);
+
+        drop(reservation);
+        assert_eq!(allocator.allocated_count(), 4, "Undrawn PIDs are freed");
+        assert!(!allocator.contains(4) && !allocator.contains(5));
+        drop(drawn);
+        assert_eq!(allocator.allocated_count(), 1);
+
+        let mut exhausted = allocator.reserve_n(1).unwrap();
+        assert!(exhausted.draw().is_some());
+        assert!(exhausted.draw().is_none());
+    }
+
+    #[test]
+    fn test_debug_summarizes_occupancy() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(4);
+        let mut pids: Vec<_> = allocator.allocations().take(3).collect();
+        drop(pids.remove(0));
+        assert_eq!(
+            std::format!("{allocator:?}"),
+            "PidAllocator { allocated: 2, free: 5, capacity: 8 }",
+            "The quarantined PID is neither allocated nor free"
+        );
+
+        let guard = allocator.lock();
+        assert_eq!(std::format!("{allocator:?}"), "PidAllocator { state: <locked> }");
+        drop(guard);
+
+        let restricted = PidAllocator::<1, u8>::with_max(6);
+        assert!(std::format!("{restricted:?}").ends_with("free: 6, capacity: 6 }"));
+    }
+
+    #[test]
+    fn test_allocations_desc_yields_strictly_descending_pids() {
+        let allocator = PidAllocator::<2, u16>::new();
+        let pids: Vec<_> = allocator.allocations_desc().collect();
+        assert_eq!(pids.len(), PidAllocator::<2, u16>::CAPACITY);
+        assert_eq!(*pids[0], PidAllocator::<2, u16>::CAPACITY - 1);
+        assert!(pids.windows(2).all(|pair| *pair[0] > *pair[1]));
+        assert_eq!(*pids[pids.len() - 1], 0);
+        assert!(allocator.allocate_highest().is_none());
+    }
+
+    #[test]
+    fn test_same_allocator_compares_provenance() {
+        let (first, second) = (PidAllocator::<ORDER>::new(), PidAllocator::<ORDER>::new());
+        let a = first.allocate().unwrap();
+        let b = first.allocate().unwrap();
+        let c = second.allocate().unwrap();
+
+        assert!(a.same_allocator(&b) && b.same_allocator(&a));
+        assert!(a.same_allocator(&a));
+        assert!(!a.same_allocator(&c) && !c.same_allocator(&a));
+
+        let forked = first.fork().allocate().unwrap();
+        assert!(!a.same_allocator(&forked), "A fork has its own state");
+    }
+
+    #[test]
+    fn test_with_seed_reproduces_allocation_sequence() {
+        let run = |seed| {
+            let allocator = PidAllocator::<ORDER>::with_seed(seed);
+            let mut pids: Vec<_> = (0..64).map(|_| allocator.allocate().unwrap()).collect();
+            // Free a few PIDs along the way so the replay covers reuse as well.
+            pids.retain(|pid| **pid % 3 != 0);
+            pids.extend((0..16).map(|_| allocator.allocate().unwrap()));
+            pids.iter().map(|pid| **pid).collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8), "Different seeds give different sequences");
+        assert_eq!(run(0), run(0));
+    }
+
+    #[test]
+    fn test_len_tracks_allocations_and_drops() {
+        let allocator = PidAllocator::<1, u8>::new();
+        assert_eq!((allocator.len(), allocator.is_empty()), (0, true));
+
+        let mut pids: Vec<_> = allocator.allocations().collect();
+        assert_eq!(allocator.len(), allocator.capacity());
+        pids.truncate(3);
+        assert_eq!(allocator.len(), 3);
+        assert_eq!(allocator.len(), allocator.allocated_count());
+
+        pids.clear();
+        assert_eq!((allocator.len(), allocator.is_empty()), (0, true));
+    }
+
+    #[test]
+    fn test_retain_frees_rejected_pids() {
+        let mut allocator = PidAllocator::<ORDER>::new();
+        allocator.extend(0..20);
+        let mut seen = Vec::new();
+        allocator.retain(|number| {
+            seen.push(number);
+            number % 2 == 0
+        });
+
+        assert_eq!(seen, (0..20).collect::<Vec<_>>(), "Each allocated PID is visited once, in order");
+        assert!((0..20).step_by(2).all(|number| allocator.contains(number)));
+        assert!((1..20).step_by(2).all(|number| !allocator.contains(number)));
+        assert_eq!(allocator.allocated_count(), 10);
+        allocator.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_allocate_at_rejects_each_reason_independently() {
+        // Beyond the bitmap, including numbers that would overflow a naive layer computation.
+        let allocator = PidAllocator::<1, u8>::new();
+        assert!(allocator.allocate_at(8).is_none());
+        assert!(allocator.allocate_at(usize::MAX).is_none());
+        assert_eq!(allocator.allocated_count(), 0);
+
+        // At or above the `with_max` bound, while the bitmap would have room.
+        let bounded = PidAllocator::<1, u8>::with_max(5);
+        assert!(bounded.allocate_at(5).is_none());
+        assert!(bounded.allocate_at(4).is_some());
+
+        // Already allocated.
+        let _held = allocator.allocate_at(3).unwrap();
+        assert!(allocator.allocate_at(3).is_none());
+
+        // Quarantined: freed, but not yet available again.
+        let quarantined = PidAllocator::<1, u8>::with_quarantine(2);
+        drop(quarantined.allocate_at(6).unwrap());
+        assert!(!quarantined.contains(6));
+        assert!(quarantined.allocate_at(6).is_none());
+
+        // Pinned: free, but blocked out.
+        let pinned = PidAllocator::<1, u8>::new();
+        pinned.pin_low(2);
+        assert!(pinned.allocate_at(1).is_none());
+        assert!(pinned.allocate_at(2).is_some());
+    }
+
+    #[test]
+    fn test_allocate_checked_distinguishes_contention_from_exhaustion() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let (locked, done) = (Barrier::new(2), Barrier::new(2));
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _guard = allocator.lock();
+                locked.wait();
+                done.wait();
+            });
+
+            locked.wait();
+            assert_eq!(allocator.allocate_checked().map(|pid| *pid), Err(PidError::WouldBlock));
+            done.wait();
+        });
+
+        let pid = allocator.allocate_checked().unwrap();
+        assert_eq!(*pid, 0);
+        let _rest: Vec<_> = allocator.allocations().collect();
+        assert_eq!(allocator.allocate_checked().map(|pid| *pid), Err(PidError::Exhausted));
+    }
+
+    #[test]
+    fn test_raw_pid_round_trips_through_integer_api() {
+        extern "C" fn register(raw: crate::RawPid) -> usize {
+            raw.get() + 1000
+        }
+
+        let allocator = PidAllocator::<ORDER>::new();
+        let _init = allocator.allocate().unwrap();
+        let pid = allocator.allocate().unwrap();
+        let raw = pid.raw();
+        assert_eq!(core::mem::size_of::<crate::RawPid>(), core::mem::size_of::<usize>());
+
+        let handle = register(raw);
+        let back = crate::RawPid::from(handle - 1000);
+        assert_eq!(back, raw);
+        assert_eq!(usize::from(back), *pid);
+        assert_eq!(back.to_string(), "1");
+
+        drop(pid);
+        assert!(!allocator.contains(raw.get()), "A RawPid does not keep its PID allocated");
+    }
+
+    #[test]
+    fn test_allocate_batch_contiguous_hands_out_adjacent_pids() {
+        let allocator = PidAllocator::<2, u8>::with_quarantine(8);
+        let blockers: Vec<_> = [1, 5, 9].map(|number| allocator.allocate_at(number).unwrap()).into();
+
+        let batch = allocator.allocate_batch_contiguous(5).unwrap();
+        assert_eq!(batch.numbers().collect::<Vec<_>>(), [10, 11, 12, 13, 14], "The run spans layers");
+        assert!(allocator.allocate_batch_contiguous(6).is_none(), "Only 16 - 15 = 1 PID is left above");
+        assert_eq!(allocator.allocated_count(), 8, "A failed request claims nothing");
+
+        let mut pids = Vec::from(batch);
+        drop(pids.remove(2));
+        assert!(!allocator.contains(12), "Dropping a middle handle frees exactly that PID");
+        assert!([10, 11, 13, 14].iter().all(|&number| allocator.contains(number)));
+
+        // PID 12 is quarantined now, so it does not count as part of a free run.
+        assert_eq!(*allocator.allocate_batch_contiguous(3).unwrap()[0], 2);
+        assert!(allocator.allocate_batch_contiguous(0).unwrap().is_empty());
+        drop(blockers);
+    }
+
+    #[test]
+    fn test_allocator_usable_after_panic_while_locked() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let worker = allocator.clone();
+        let result = thread::spawn(move || {
+            let mut guard = worker.lock();
+            let number = guard.allocate().unwrap();
+            panic!("deliberate panic holding the lock after allocating {number}");
+        })
+        .join();
+
+        assert!(result.is_err(), "The worker thread should have panicked");
+        assert!(allocator.contains(0), "Numbers allocated under the guard stay allocated");
+        assert_eq!(*allocator.allocate().unwrap(), 1, "The spin lock is released, not poisoned");
+        assert_eq!(allocator.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_allocate_n_rollback_with_pin_floor_and_quarantine() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(1);
+        allocator.set_policy(AllocationPolicy::RoundRobin);
+        let low = allocator.allocate().unwrap();
+        allocator.pin_low(1);
+        drop(low);
+        let (bytes, next) = (allocator.as_bytes(), allocator.peek_next());
+        let total = allocator.total_allocations();
+
+        // PID 0 leaves the quarantine during the batch and is pinned, since it lies below the floor.
+        assert!(allocator.allocate_n(8).is_none());
+        assert_eq!(allocator.allocated_count(), 0, "PID 0 must not be counted as both quarantined and pinned");
+        assert_eq!(allocator.as_bytes(), bytes);
+        assert_eq!(allocator.peek_next(), next, "The round-robin cursor is restored");
+        assert_eq!(allocator.total_allocations(), total, "Rolled-back PIDs are not counted");
+        assert_eq!(allocator.failed_allocations(), 1, "The failed batch counts once");
+        assert_eq!(allocator.check_invariants(), Ok(()));
+
+        let batch = allocator.allocate_n(7).unwrap();
+        assert_eq!(batch.numbers().collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(allocator.allocated_count(), 7);
+        assert!(allocator.allocate().is_none(), "PID 0 is pinned once it leaves the quarantine");
+    }
+
+    #[test]
+    fn test_allocate_nonzero_counts_only_the_handed_out_pid() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(2);
+        let first = allocator.allocate_nonzero().unwrap();
+        assert_eq!(first.get(), 1);
+        assert_eq!(allocator.total_allocations(), 1, "Skipping PID 0 must not count an allocation");
+        drop(first);
+
+        let second = allocator.allocate_nonzero().unwrap();
+        let third = allocator.allocate_nonzero().unwrap();
+        assert_eq!((second.get(), third.get()), (2, 3), "PID 1 sits out two allocations");
+        assert_eq!(allocator.total_allocations(), 3);
+        assert_eq!(allocator.peek_next(), Some(0));
+        assert_eq!(allocator.check_invariants(), Ok(()));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_allocate_async_spurious_poll_does_not_lose_wakeups() {
+        use core::{future::Future, pin::Pin, sync::atomic::AtomicBool, task::Context};
+        use std::task::{Wake, Waker};
+
+        struct Flag(AtomicBool);
+
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let flag = || Arc::new(Flag(AtomicBool::new(false)));
+        let poll = |future: &mut Pin<std::boxed::Box<dyn Future<Output = _> + '_>>, flag: &Arc<Flag>| {
+            let waker = Waker::from(flag.clone());
+            future.as_mut().poll(&mut Context::from_waker(&waker))
+        };
+
+        let allocator = PidAllocator::<1, u8>::new();
+        let mut pids: Vec<_> = allocator.allocations().collect();
+        let (first_flag, second_flag) = (flag(), flag());
+        let mut first: Pin<std::boxed::Box<dyn Future<Output = _>>> = std::boxed::Box::pin(allocator.allocate_async());
+        let mut second: Pin<std::boxed::Box<dyn Future<Output = _>>> = std::boxed::Box::pin(allocator.allocate_async());
+        assert!(poll(&mut first, &first_flag).is_pending());
+        assert!(poll(&mut second, &second_flag).is_pending());
+
+        // The freed PID wakes the first waiter, but the still-queued second one takes it on a spurious poll.
+        drop(pids.pop());
+        assert!(first_flag.0.load(Ordering::SeqCst));
+        let taken = poll(&mut second, &second_flag);
+        assert!(taken.is_ready());
+        assert!(poll(&mut first, &first_flag).is_pending());
+
+        first_flag.0.store(false, Ordering::SeqCst);
+        drop(pids.pop());
+        assert!(first_flag.0.load(Ordering::SeqCst), "The next free must wake the first waiter");
+        assert!(poll(&mut first, &first_flag).is_ready());
+        drop((first, second, taken));
+
+        // Two futures polled by one task share a waker; cancelling one must not deregister the other.
+        let shared = flag();
+        let mut kept: Pin<std::boxed::Box<dyn Future<Output = _>>> = std::boxed::Box::pin(allocator.allocate_async());
+        let mut cancelled: Pin<std::boxed::Box<dyn Future<Output = _>>> = std::boxed::Box::pin(allocator.allocate_async());
+        let _rest: Vec<_> = allocator.allocations().collect();
+        assert!(poll(&mut kept, &shared).is_pending());
+        assert!(poll(&mut cancelled, &shared).is_pending());
+        drop(cancelled);
+        drop(pids.pop());
+        assert!(shared.0.load(Ordering::SeqCst), "The remaining sibling is still registered");
+        assert!(poll(&mut kept, &shared).is_ready());
+    }
+
+    #[test]
+    fn test_allocate_n_more_than_capacity_returns_none() {
+        let allocator = PidAllocator::<1>::with_quarantine(2);
+        drop(allocator.allocate());
+        assert!(allocator.allocate_n(usize::MAX).is_none());
+        assert!(allocator.allocate_n(PidAllocator::<1>::CAPACITY + 1).is_none());
+        assert_eq!((allocator.allocated_count(), allocator.failed_allocations()), (0, 2));
+
+        let all = allocator.allocate_n(PidAllocator::<1>::CAPACITY).unwrap();
+        assert_eq!(all.len(), PidAllocator::<1>::CAPACITY, "The quarantined PID is released during the batch");
+    }
+
+    #[test]
+    fn test_reserve_n_more_than_capacity_returns_none() {
+        let allocator = PidAllocator::<1, u8>::new();
+        assert!(allocator.reserve_n(usize::MAX).is_none());
+        assert!(allocator.reserve_n(9).is_none());
+        assert_eq!(allocator.allocated_count(), 0);
+        assert_eq!(allocator.reserve_n(8).unwrap().remaining(), 8);
+    }
+
+    #[cfg(feature = "rand_core")]
+    #[test]
+    fn test_allocate_random_releases_due_quarantine() {
+        let allocator = PidAllocator::<1, u8>::with_quarantine(1);
+        let mut pids: Vec<_> = (0..7).map(|_| allocator.allocate().unwrap()).collect();
+        drop(pids.remove(0));
+
+        let mut rng = SplitMix64(3);
+        let last = allocator.allocate_random(&mut rng).unwrap();
+        assert_eq!(*last, 7);
+        let released = allocator.allocate_random(&mut rng);
+        assert_eq!(
+            released.as_deref(),
+            Some(&0),
+            "PID 0 has sat out its allocation and must be visible to random allocation"
+        );
+        assert!(allocator.allocate_random(&mut rng).is_none());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_recovered_allocator_shares_contention_count() {
+        let allocator = PidAllocator::<1, u8>::new();
+        let pid = allocator.allocate().unwrap();
+        let recovered = pid.allocator();
+
+        let guard = allocator.lock();
+        assert_eq!(recovered.try_allocate_nonblocking().map(|pid| pid.is_some()), Err(PidError::WouldBlock));
+        drop(guard);
+
+        assert_eq!(recovered.contention_count(), 1);
+        assert_eq!(allocator.contention_count(), 1, "Contention seen through the recovered handle is shared");
+        assert_eq!(allocator.clone().contention_count(), 1);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_cache_refill_counts_contention() {
+        let allocator = PidAllocator::<ORDER>::new();
+        let cache = PidCache::new(&allocator, 4);
+        let locked = Barrier::new(2);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let _guard = allocator.lock();
+                locked.wait();
+                // Hold the lock long enough for the refill below to find it taken.
+                thread::sleep(Duration::from_millis(100));
+            });
+
+            locked.wait();
+            assert!(cache.allocate().is_some());
+        });
+        assert_eq!(allocator.contention_count(), 1, "The refill had to wait for the lock");
+    }
+
+    #[test]
+    fn test_try_narrow_keeps_quarantine_pin_and_sequences() {
+        let quarantined = PidAllocator::<4, u8>::with_quarantine(2);
+        let pids: Vec<_> = (0..3).map(|_| quarantined.allocate().unwrap()).collect();
+        quarantined.pin_low(1);
+        drop(pids);
+
+        let narrow = quarantined.try_narrow::<2>().unwrap();
+        assert_eq!(narrow.allocated_count(), 0);
+        assert_eq!(*narrow.allocate().unwrap(), 3, "Quarantined PIDs are not reused immediately");
+        assert_eq!(narrow.check_invariants(), Ok(()));
+
+        let tracked = PidAllocator::<4, u8>::with_allocation_seq();
+        let _first = tracked.allocate().unwrap();
+        let narrow = tracked.try_narrow::<1>().unwrap();
+        let second = narrow.allocate().unwrap();
+        assert_eq!((narrow.allocation_seq(0), narrow.allocation_seq(*second)), (Some(1), Some(2)));
+    }
@@ -0,0 +1,117 @@
+use core::{
+    fmt::Debug,
+    hash::Hash,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Not},
+};
+
+/// An unsigned integer type that can be used as a bitmap layer word.
+///
+/// Each layer of a [`PidAllocator`](crate::PidAllocator) is a single word whose bits track the
+/// allocation state of `W::BITS` consecutive PIDs. Choosing a narrower or wider word changes the
+/// capacity of the allocator (`ORDER * W::BITS`) and the layout of its exported bitmap.
+///
+/// Only the layer word is generic, not the PID integer type: handles dereference to `usize`, and
+/// methods such as `allocate_at`, `contains` and `try_recycle` take `usize` numbers, whatever `W`
+/// is. Where an external ABI needs narrower PIDs, convert at the boundary, e.g. with
+/// `u32::try_from(*pid)`. The conversion cannot fail while the allocator's
+/// [`CAPACITY`](crate::PidAllocator::CAPACITY) fits the target type.
+///
+/// This trait is implemented for `u8`, `u16`, `u32`, `u64`, `u128` and `usize`.
+pub trait BitWord:
+    Copy
+    + Eq
+    + Hash
+    + Debug
+    + Not<Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitAndAssign
+    + BitOrAssign
+{
+    /// The number of bits in the word, and thus the number of PIDs per layer.
+    const BITS: u32;
+    /// The number of bytes the word occupies in an exported bitmap.
+    const BYTES: usize;
+    /// A word with no bits set.
+    const ZERO: Self;
+    /// A word with all bits set.
+    const MAX: Self;
+
+    /// Returns a word with only the bit at `index` set.
+    fn bit(index: usize) -> Self;
+
+    /// Returns a word with the lowest `count` bits set. A `count` of `Self::BITS` or more saturates
+    /// to [`BitWord::MAX`], so callers may pass a bound that lies beyond the word.
+    fn low_mask(count: usize) -> Self;
+
+    /// Returns the number of trailing zeros in the binary representation of `self`.
+    fn trailing_zeros(self) -> u32;
+
+    /// Returns the number of leading zeros in the binary representation of `self`.
+    fn leading_zeros(self) -> u32;
+
+    /// Returns the number of ones in the binary representation of `self`.
+    fn count_ones(self) -> u32;
+
+    /// Writes the word into `out` as little-endian bytes. `out` must be `Self::BYTES` long.
+    fn write_le_bytes(self, out: &mut [u8]);
+
+    /// Reads a word from little-endian bytes. `bytes` must be `Self::BYTES` long.
+    fn read_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_bit_word {
+    ($($ty:ty),*) => {
+        $(
+            impl BitWord for $ty {
+                const BITS: u32 = <$ty>::BITS;
+                const BYTES: usize = core::mem::size_of::<$ty>();
+                const ZERO: Self = 0;
+                const MAX: Self = <$ty>::MAX;
+
+                #[inline]
+                fn bit(index: usize) -> Self {
+                    1 << index
+                }
+
+                #[inline]
+                fn low_mask(count: usize) -> Self {
+                    if count >= <$ty>::BITS as usize {
+                        <$ty>::MAX
+                    } else {
+                        (1 << count) - 1
+                    }
+                }
+
+                #[inline]
+                fn trailing_zeros(self) -> u32 {
+                    <$ty>::trailing_zeros(self)
+                }
+
+                #[inline]
+                fn leading_zeros(self) -> u32 {
+                    <$ty>::leading_zeros(self)
+                }
+
+                #[inline]
+                fn count_ones(self) -> u32 {
+                    <$ty>::count_ones(self)
+                }
+
+                #[inline]
+                fn write_le_bytes(self, out: &mut [u8]) {
+                    out.copy_from_slice(&self.to_le_bytes());
+                }
+
+                #[inline]
+                fn read_le_bytes(bytes: &[u8]) -> Self {
+                    let mut word = [0; core::mem::size_of::<$ty>()];
+                    word.copy_from_slice(bytes);
+                    <$ty>::from_le_bytes(word)
+                }
+            }
+        )*
+    };
+}
+
+impl_bit_word!(u8, u16, u32, u64, u128, usize);